@@ -0,0 +1,197 @@
+// compare the ring buffer Channel with the Mutex<VecDeque> backend it replaced and tokio mpsc,
+// the workload is the same as test_channel_wait: many senders and receivers on one bounded channel
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use std::collections::VecDeque;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Waker};
+use wd_tools::channel::Channel;
+
+const CAP: usize = 100;
+const ITEMS: usize = 10_000;
+
+// the previous backend: one Mutex around the items and both waker queues
+mod locked {
+    use super::*;
+
+    struct WaitDeque<T> {
+        deque: VecDeque<T>,
+        sender_waker: VecDeque<Waker>,
+        receiver_waker: VecDeque<Waker>,
+    }
+
+    pub struct LockedChannel<T> {
+        cap: usize,
+        wait_deque: Arc<Mutex<WaitDeque<T>>>,
+    }
+
+    impl<T> Clone for LockedChannel<T> {
+        fn clone(&self) -> Self {
+            Self {
+                cap: self.cap,
+                wait_deque: self.wait_deque.clone(),
+            }
+        }
+    }
+
+    impl<T> LockedChannel<T> {
+        pub fn new(cap: usize) -> Self {
+            Self {
+                cap,
+                wait_deque: Arc::new(Mutex::new(WaitDeque {
+                    deque: VecDeque::with_capacity(cap),
+                    sender_waker: VecDeque::new(),
+                    receiver_waker: VecDeque::new(),
+                })),
+            }
+        }
+        pub fn send(&self, value: T) -> SendFuture<T> {
+            SendFuture {
+                data: Some(value),
+                chan: self.clone(),
+            }
+        }
+        pub fn recv(&self) -> RecvFuture<T> {
+            RecvFuture { chan: self.clone() }
+        }
+    }
+
+    pub struct SendFuture<T> {
+        data: Option<T>,
+        chan: LockedChannel<T>,
+    }
+
+    impl<T> Unpin for SendFuture<T> {}
+
+    impl<T> Future for SendFuture<T> {
+        type Output = ();
+
+        fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+            let this = &mut *self;
+            let mut lock = this.chan.wait_deque.lock().unwrap();
+            if lock.deque.len() >= this.chan.cap {
+                lock.sender_waker.push_back(cx.waker().clone());
+                return Poll::Pending;
+            }
+            lock.deque.push_back(this.data.take().unwrap());
+            if let Some(w) = lock.receiver_waker.pop_front() {
+                w.wake();
+            }
+            Poll::Ready(())
+        }
+    }
+
+    pub struct RecvFuture<T> {
+        chan: LockedChannel<T>,
+    }
+
+    impl<T> Future for RecvFuture<T> {
+        type Output = T;
+
+        fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<T> {
+            let mut lock = self.chan.wait_deque.lock().unwrap();
+            match lock.deque.pop_front() {
+                Some(t) => {
+                    if let Some(w) = lock.sender_waker.pop_front() {
+                        w.wake();
+                    }
+                    Poll::Ready(t)
+                }
+                None => {
+                    lock.receiver_waker.push_back(cx.waker().clone());
+                    Poll::Pending
+                }
+            }
+        }
+    }
+}
+
+async fn ring_channel(tasks: usize) {
+    let (sender, receiver) = Channel::<usize>::new(CAP);
+    let mut handles = vec![];
+    for _ in 0..tasks {
+        let sender = sender.clone();
+        handles.push(tokio::spawn(async move {
+            for i in 0..ITEMS {
+                sender.send(i).await.unwrap();
+            }
+        }));
+        let receiver = receiver.clone();
+        handles.push(tokio::spawn(async move {
+            for _ in 0..ITEMS {
+                receiver.recv().await.unwrap();
+            }
+        }));
+    }
+    for h in handles {
+        h.await.unwrap();
+    }
+}
+
+async fn locked_channel(tasks: usize) {
+    let chan = locked::LockedChannel::<usize>::new(CAP);
+    let mut handles = vec![];
+    for _ in 0..tasks {
+        let sender = chan.clone();
+        handles.push(tokio::spawn(async move {
+            for i in 0..ITEMS {
+                sender.send(i).await;
+            }
+        }));
+        let receiver = chan.clone();
+        handles.push(tokio::spawn(async move {
+            for _ in 0..ITEMS {
+                receiver.recv().await;
+            }
+        }));
+    }
+    for h in handles {
+        h.await.unwrap();
+    }
+}
+
+// tokio mpsc has a single consumer
+async fn tokio_mpsc(tasks: usize) {
+    let (sender, mut receiver) = tokio::sync::mpsc::channel::<usize>(CAP);
+    let mut handles = vec![];
+    for _ in 0..tasks {
+        let sender = sender.clone();
+        handles.push(tokio::spawn(async move {
+            for i in 0..ITEMS {
+                sender.send(i).await.unwrap();
+            }
+        }));
+    }
+    for _ in 0..tasks * ITEMS {
+        receiver.recv().await.unwrap();
+    }
+    for h in handles {
+        h.await.unwrap();
+    }
+}
+
+fn bench_channel(c: &mut Criterion) {
+    let rt = tokio::runtime::Builder::new_multi_thread()
+        .worker_threads(4)
+        .enable_all()
+        .build()
+        .unwrap();
+    let mut group = c.benchmark_group("channel");
+    group.sample_size(10);
+    for tasks in [1, 4, 16] {
+        group.bench_with_input(BenchmarkId::new("ring", tasks), &tasks, |b, &n| {
+            b.to_async(&rt).iter(|| ring_channel(n))
+        });
+        group.bench_with_input(BenchmarkId::new("mutex", tasks), &tasks, |b, &n| {
+            b.to_async(&rt).iter(|| locked_channel(n))
+        });
+        group.bench_with_input(BenchmarkId::new("tokio_mpsc", tasks), &tasks, |b, &n| {
+            b.to_async(&rt).iter(|| tokio_mpsc(n))
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_channel);
+criterion_main!(benches);
@@ -0,0 +1,208 @@
+use crate::channel::{Channel, ChannelError, ChannelResult, Receiver, RecvError};
+use pin_project_lite::pin_project;
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll, Waker};
+use std::time::Duration;
+use tokio::time::Sleep;
+
+pin_project! {
+    /// 批量接收，收到的元素追加到调用方的buf里，future被drop时已收到的元素也不会丢
+    pub struct RecvManyFuture<'a, T> {
+        chan: Channel<T>,
+        buf: &'a mut Vec<T>,
+        // the length of buf before the call
+        start: usize,
+        max: usize,
+        // wait for more items after the first one, None returns at once
+        linger: Option<Duration>,
+        #[pin]
+        sleep: Option<Sleep>,
+        waker: Option<Waker>,
+    }
+    impl<'a, T> PinnedDrop for RecvManyFuture<'a, T> {
+        fn drop(this: Pin<&mut Self>) {
+            let this = this.project();
+            if let Some(w) = this.waker.take() {
+                this.chan.cancel_recv_waker(&w);
+            }
+        }
+    }
+}
+
+impl<T> Future for RecvManyFuture<'_, T> {
+    // the number of items appended to buf
+    type Output = ChannelResult<usize, RecvError>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut this = self.project();
+        let end = *this.start + *this.max;
+        let closed = match this
+            .chan
+            ._try_recv_many(this.buf, end, Some(cx.waker()))
+        {
+            Ok(o) => o,
+            Err(e) => return Poll::Ready(Err(e)),
+        };
+        let n = this.buf.len() - *this.start;
+        let ready = if n >= *this.max || closed {
+            true
+        } else if n == 0 {
+            false
+        } else if let Some(linger) = *this.linger {
+            if this.sleep.is_none() {
+                this.sleep.set(Some(tokio::time::sleep(linger)));
+            }
+            this.sleep.as_pin_mut().unwrap().poll(cx).is_ready()
+        } else {
+            true
+        };
+        if !ready {
+            *this.waker = Some(cx.waker().clone());
+            return Poll::Pending;
+        }
+        if let Some(w) = this.waker.take() {
+            this.chan.cancel_recv_waker(&w);
+        }
+        if n == 0 {
+            return Poll::Ready(RecvError::CLOSED.into_err());
+        }
+        Poll::Ready(Ok(n))
+    }
+}
+
+impl<T> Channel<T> {
+    // wait for at least one item, then append up to max items to buf
+    pub fn recv_many<'a>(&self, buf: &'a mut Vec<T>, max: usize) -> RecvManyFuture<'a, T> {
+        self.recv_many_inner(buf, max, None)
+    }
+    // wait for at least one item, then keep collecting until max items or linger elapses
+    pub fn recv_batch<'a>(
+        &self,
+        buf: &'a mut Vec<T>,
+        max: usize,
+        linger: Duration,
+    ) -> RecvManyFuture<'a, T> {
+        self.recv_many_inner(buf, max, Some(linger))
+    }
+    fn recv_many_inner<'a>(
+        &self,
+        buf: &'a mut Vec<T>,
+        max: usize,
+        linger: Option<Duration>,
+    ) -> RecvManyFuture<'a, T> {
+        let max = max.max(1);
+        buf.reserve(max.min(1024));
+        RecvManyFuture {
+            chan: self.clone(),
+            start: buf.len(),
+            max,
+            buf,
+            linger,
+            sleep: None,
+            waker: None,
+        }
+    }
+}
+
+impl<T> Receiver<T> {
+    pub fn recv_many<'a>(&self, buf: &'a mut Vec<T>, max: usize) -> RecvManyFuture<'a, T> {
+        self.channel().recv_many(buf, max)
+    }
+    pub fn recv_batch<'a>(
+        &self,
+        buf: &'a mut Vec<T>,
+        max: usize,
+        linger: Duration,
+    ) -> RecvManyFuture<'a, T> {
+        self.channel().recv_batch(buf, max, linger)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::channel::{Channel, RecvError};
+    use std::time::{Duration, Instant};
+
+    #[tokio::test]
+    async fn test_recv_many() {
+        let (sender, receiver) = Channel::<usize>::new(10);
+        for i in 0..5 {
+            sender.try_send(i).unwrap();
+        }
+        let mut buf = vec![];
+        assert_eq!(receiver.recv_many(&mut buf, 3).await, Ok(3));
+        assert_eq!(buf, vec![0, 1, 2]);
+        assert_eq!(receiver.recv_many(&mut buf, 3).await, Ok(2));
+        assert_eq!(buf, vec![0, 1, 2, 3, 4]);
+        buf.clear();
+
+        let waiting = sender.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+            waiting.send(5).await.unwrap();
+        });
+        assert_eq!(receiver.recv_many(&mut buf, 3).await, Ok(1));
+        assert_eq!(buf, vec![5]);
+        buf.clear();
+
+        sender.try_send(6).unwrap();
+        sender.close();
+        assert_eq!(receiver.recv_many(&mut buf, 3).await, Ok(1));
+        assert_eq!(buf, vec![6]);
+        assert_eq!(
+            receiver.recv_many(&mut buf, 3).await,
+            Err(RecvError::CLOSED)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_recv_batch() {
+        let (sender, receiver) = Channel::<usize>::new(10);
+        let producer = sender.clone();
+        tokio::spawn(async move {
+            for i in 0..3 {
+                producer.send(i).await.unwrap();
+                tokio::time::sleep(Duration::from_millis(5)).await;
+            }
+        });
+        let mut buf = vec![];
+        let n = receiver
+            .recv_batch(&mut buf, 3, Duration::from_millis(500))
+            .await
+            .unwrap();
+        assert_eq!(n, 3);
+        assert_eq!(buf, vec![0, 1, 2]);
+        buf.clear();
+
+        sender.try_send(3).unwrap();
+        let start = Instant::now();
+        let n = receiver
+            .recv_batch(&mut buf, 3, Duration::from_millis(20))
+            .await
+            .unwrap();
+        assert_eq!(n, 1);
+        assert_eq!(buf, vec![3]);
+        assert!(start.elapsed() >= Duration::from_millis(20));
+    }
+
+    #[tokio::test]
+    async fn test_recv_batch_cancel() {
+        let (sender, receiver) = Channel::<usize>::new(10);
+        sender.try_send(0).unwrap();
+        sender.try_send(1).unwrap();
+        let mut buf = vec![];
+        // dropped while lingering, the items taken so far stay in buf
+        let res = tokio::time::timeout(
+            Duration::from_millis(20),
+            receiver.recv_batch(&mut buf, 10, Duration::from_millis(500)),
+        )
+        .await;
+        assert!(res.is_err());
+        assert_eq!(buf, vec![0, 1]);
+
+        sender.try_send(2).unwrap();
+        assert_eq!(receiver.recv_many(&mut buf, 10).await, Ok(1));
+        assert_eq!(buf, vec![0, 1, 2]);
+    }
+}
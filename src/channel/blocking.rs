@@ -0,0 +1,212 @@
+use crate::channel::{
+    Channel, ChannelError, ChannelResult, Receiver, RecvError, SendError, Sender,
+};
+use std::sync::Arc;
+use std::task::{Wake, Waker};
+use std::thread::Thread;
+use std::time::{Duration, Instant};
+
+// wake a parked thread, it is registered in the same waker queues as async tasks
+struct ThreadWaker(Thread);
+
+impl Wake for ThreadWaker {
+    fn wake(self: Arc<Self>) {
+        self.0.unpark()
+    }
+    fn wake_by_ref(self: &Arc<Self>) {
+        self.0.unpark()
+    }
+}
+
+fn thread_waker() -> Waker {
+    Waker::from(Arc::new(ThreadWaker(std::thread::current())))
+}
+
+// park until woken or the deadline passes, return false if timeout
+fn park(deadline: Option<Instant>) -> bool {
+    match deadline {
+        None => {
+            std::thread::park();
+            true
+        }
+        Some(d) => {
+            let now = Instant::now();
+            if now >= d {
+                return false;
+            }
+            std::thread::park_timeout(d - now);
+            true
+        }
+    }
+}
+
+// the blocking versions are for std threads, do not call them in an async task
+impl<T> Channel<T> {
+    pub fn blocking_send(&self, value: T) -> ChannelResult<(), SendError<T>> {
+        self.blocking_send_inner(value, None)
+    }
+    pub fn blocking_send_timeout(
+        &self,
+        value: T,
+        timeout: Duration,
+    ) -> ChannelResult<(), SendError<T>> {
+        self.blocking_send_inner(value, Some(Instant::now() + timeout))
+    }
+    fn blocking_send_inner(
+        &self,
+        value: T,
+        deadline: Option<Instant>,
+    ) -> ChannelResult<(), SendError<T>> {
+        let waker = thread_waker();
+        let mut data = Some(value);
+        // the waker may still be parked after an early unpark, remove it on every exit
+        loop {
+            match self.poll_send(&mut data, &waker) {
+                Ok(false) => {}
+                res => {
+                    self.remove_send_waker(&waker);
+                    return res.map(|_| ());
+                }
+            }
+            if !park(deadline) {
+                self.cancel_send_waker(&waker);
+                return match data.take() {
+                    Some(d) => SendError::TIMEOUT(d).into_err(),
+                    None => Ok(()),
+                };
+            }
+        }
+    }
+    pub fn blocking_recv(&self) -> ChannelResult<T, RecvError> {
+        self.blocking_recv_inner(None)
+    }
+    pub fn blocking_recv_timeout(&self, timeout: Duration) -> ChannelResult<T, RecvError> {
+        self.blocking_recv_inner(Some(Instant::now() + timeout))
+    }
+    fn blocking_recv_inner(&self, deadline: Option<Instant>) -> ChannelResult<T, RecvError> {
+        let waker = thread_waker();
+        loop {
+            match self._try_recv(Some(&waker)) {
+                Err(RecvError::EMPTY) => {}
+                res => {
+                    self.remove_recv_waker(&waker);
+                    return res;
+                }
+            }
+            if !park(deadline) {
+                self.cancel_recv_waker(&waker);
+                return RecvError::TIMEOUT.into_err();
+            }
+        }
+    }
+}
+
+impl<T> Sender<T> {
+    pub fn blocking_send(&self, value: T) -> ChannelResult<(), SendError<T>> {
+        self.channel().blocking_send(value)
+    }
+    pub fn blocking_send_timeout(
+        &self,
+        value: T,
+        timeout: Duration,
+    ) -> ChannelResult<(), SendError<T>> {
+        self.channel().blocking_send_timeout(value, timeout)
+    }
+}
+
+impl<T> Receiver<T> {
+    pub fn blocking_recv(&self) -> ChannelResult<T, RecvError> {
+        self.channel().blocking_recv()
+    }
+    pub fn blocking_recv_timeout(&self, timeout: Duration) -> ChannelResult<T, RecvError> {
+        self.channel().blocking_recv_timeout(timeout)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::channel::{Channel, RecvError, SendError};
+    use std::future::Future;
+    use std::sync::Arc;
+    use std::task::{Context, Wake, Waker};
+    use std::time::Duration;
+
+    struct NoopWake;
+
+    impl Wake for NoopWake {
+        fn wake(self: Arc<Self>) {}
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_blocking() {
+        let (sender, receiver) = Channel::<usize>::new(2);
+        let workers = (0..4)
+            .map(|n| {
+                let sender = sender.clone();
+                std::thread::spawn(move || {
+                    for i in 0..100 {
+                        sender.blocking_send(n * 100 + i).unwrap();
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+        drop(sender);
+        let mut list = vec![];
+        while let Ok(i) = receiver.recv().await {
+            list.push(i);
+        }
+        for w in workers {
+            w.join().unwrap();
+        }
+        list.sort();
+        assert_eq!(list, (0..400).collect::<Vec<_>>());
+
+        let (sender, receiver) = Channel::<usize>::new(1);
+        let consumer = std::thread::spawn(move || {
+            let first = receiver.blocking_recv();
+            let second = receiver.blocking_recv_timeout(Duration::from_millis(10));
+            (first, second)
+        });
+        sender.send(1).await.unwrap();
+        let (first, second) = tokio::task::spawn_blocking(move || consumer.join().unwrap())
+            .await
+            .unwrap();
+        assert_eq!(first, Ok(1));
+        assert_eq!(second, Err(RecvError::TIMEOUT));
+
+        let (sender, _receiver) = Channel::<usize>::new(1);
+        sender.try_send(1).unwrap();
+        let res =
+            std::thread::spawn(move || sender.blocking_send_timeout(2, Duration::from_millis(10)))
+                .join()
+                .unwrap();
+        assert_eq!(res, Err(SendError::TIMEOUT(2)));
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_blocking_early_unpark() {
+        let (sender, receiver) = Channel::<usize>::new(1);
+        let async_receiver = receiver.clone();
+        // parked in front of the blocking receiver
+        let mut recv = Box::pin(async_receiver.recv());
+        let waker = Waker::from(Arc::new(NoopWake));
+        let pending = recv.as_mut().poll(&mut Context::from_waker(&waker));
+        assert!(pending.is_pending());
+        let consumer = std::thread::spawn(move || receiver.blocking_recv());
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        // the notification goes to the async receiver, the thread takes the item after an unpark
+        sender.try_send(1).unwrap();
+        consumer.thread().unpark();
+        let res = tokio::task::spawn_blocking(move || consumer.join().unwrap())
+            .await
+            .unwrap();
+        assert_eq!(res, Ok(1));
+
+        // the waker of the finished blocking_recv does not take the next notification
+        let waiting = tokio::spawn(recv);
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        sender.try_send(2).unwrap();
+        let res = tokio::time::timeout(Duration::from_secs(1), waiting).await;
+        assert_eq!(res.unwrap().unwrap(), Ok(2));
+    }
+}
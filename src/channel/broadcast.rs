@@ -0,0 +1,247 @@
+use crate::channel::{ChannelError, ChannelResult, RecvError, SendError};
+use std::collections::VecDeque;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Waker};
+
+/// 广播通道
+/// 每个订阅者都能收到所有消息，环形缓冲区满了会覆盖最旧的消息，慢的订阅者会收到LAGGED(n)
+#[derive(Debug)]
+pub struct Broadcast<T> {
+    status: AtomicBool,
+    cap: usize,
+    ring: Mutex<Ring<T>>,
+}
+
+#[derive(Debug)]
+struct Ring<T> {
+    buffer: VecDeque<T>,
+    // sequence of buffer[0]
+    head: u64,
+    receiver_waker: Vec<Waker>,
+}
+
+impl<T> Ring<T> {
+    fn tail(&self) -> u64 {
+        self.head + self.buffer.len() as u64
+    }
+}
+
+#[derive(Debug)]
+pub struct BroadcastSender<T> {
+    chan: Arc<Broadcast<T>>,
+}
+
+#[derive(Debug)]
+pub struct BroadcastReceiver<T> {
+    chan: Arc<Broadcast<T>>,
+    next: u64,
+}
+
+impl<T> Clone for BroadcastSender<T> {
+    fn clone(&self) -> Self {
+        Self {
+            chan: self.chan.clone(),
+        }
+    }
+}
+
+// the clone receives from the same position
+impl<T> Clone for BroadcastReceiver<T> {
+    fn clone(&self) -> Self {
+        Self {
+            chan: self.chan.clone(),
+            next: self.next,
+        }
+    }
+}
+
+impl<T> Broadcast<T> {
+    #[allow(clippy::new_ret_no_self)]
+    pub fn new(cap: usize) -> (BroadcastSender<T>, BroadcastReceiver<T>) {
+        let cap = cap.max(1);
+        let chan = Arc::new(Broadcast {
+            status: AtomicBool::new(true),
+            cap,
+            ring: Mutex::new(Ring {
+                buffer: VecDeque::with_capacity(cap),
+                head: 0,
+                receiver_waker: Vec::new(),
+            }),
+        });
+        let receiver = BroadcastReceiver {
+            chan: chan.clone(),
+            next: 0,
+        };
+        (BroadcastSender { chan }, receiver)
+    }
+    fn subscribe(self: &Arc<Self>) -> BroadcastReceiver<T> {
+        let next = self.ring.lock().unwrap().tail();
+        BroadcastReceiver {
+            chan: self.clone(),
+            next,
+        }
+    }
+    fn close(&self) {
+        self.status.store(false, Ordering::Relaxed);
+        let mut ring = self.ring.lock().unwrap();
+        for i in ring.receiver_waker.drain(..) {
+            i.wake();
+        }
+    }
+    fn is_closed(&self) -> bool {
+        !self.status.load(Ordering::Relaxed)
+    }
+}
+
+impl<T> BroadcastSender<T> {
+    // never wait, the oldest message is overwritten if the ring is full
+    pub fn send(&self, value: T) -> ChannelResult<(), SendError<T>> {
+        if self.chan.is_closed() {
+            return SendError::CLOSED(value).into_err();
+        }
+        let mut ring = match self.chan.ring.lock() {
+            Ok(o) => o,
+            Err(e) => return SendError::UNKNOWN(value, e.to_string()).into_err(),
+        };
+        if ring.buffer.len() >= self.chan.cap {
+            ring.buffer.pop_front();
+            ring.head += 1;
+        }
+        ring.buffer.push_back(value);
+        for i in ring.receiver_waker.drain(..) {
+            i.wake();
+        }
+        Ok(())
+    }
+    // the new receiver only receives messages sent after subscribe
+    pub fn subscribe(&self) -> BroadcastReceiver<T> {
+        self.chan.subscribe()
+    }
+    pub fn close(&self) {
+        self.chan.close()
+    }
+    pub fn is_closed(&self) -> bool {
+        self.chan.is_closed()
+    }
+}
+
+impl<T: Clone> BroadcastReceiver<T> {
+    pub fn try_recv(&mut self) -> ChannelResult<T, RecvError> {
+        self._try_recv(None)
+    }
+    pub fn recv(&mut self) -> BroadcastRecvFuture<'_, T> {
+        BroadcastRecvFuture { receiver: self }
+    }
+    fn _try_recv(&mut self, waker: Option<&Waker>) -> ChannelResult<T, RecvError> {
+        let mut ring = match self.chan.ring.lock() {
+            Ok(o) => o,
+            Err(e) => return RecvError::UNKNOWN(e.to_string()).into_err(),
+        };
+        if self.next < ring.head {
+            let lagged = ring.head - self.next;
+            self.next = ring.head;
+            return RecvError::LAGGED(lagged).into_err();
+        }
+        let index = (self.next - ring.head) as usize;
+        if let Some(s) = ring.buffer.get(index) {
+            self.next += 1;
+            return Ok(s.clone());
+        }
+        if self.chan.is_closed() {
+            return RecvError::CLOSED.into_err();
+        }
+        if let Some(w) = waker {
+            if !ring.receiver_waker.iter().any(|i| i.will_wake(w)) {
+                ring.receiver_waker.push(w.clone());
+            }
+        }
+        RecvError::EMPTY.into_err()
+    }
+}
+
+impl<T> BroadcastReceiver<T> {
+    pub fn subscribe(&self) -> BroadcastReceiver<T> {
+        self.chan.subscribe()
+    }
+    // the number of messages not received yet
+    pub fn len(&self) -> usize {
+        let ring = self.chan.ring.lock().unwrap();
+        (ring.tail() - self.next.max(ring.head)) as usize
+    }
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+    pub fn close(&self) {
+        self.chan.close()
+    }
+    pub fn is_closed(&self) -> bool {
+        self.chan.is_closed()
+    }
+}
+
+pub struct BroadcastRecvFuture<'a, T> {
+    receiver: &'a mut BroadcastReceiver<T>,
+}
+
+impl<T: Clone> Future for BroadcastRecvFuture<'_, T> {
+    type Output = ChannelResult<T, RecvError>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        match self.receiver._try_recv(Some(cx.waker())) {
+            Err(RecvError::EMPTY) => Poll::Pending,
+            res => Poll::Ready(res),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::channel::{Broadcast, RecvError, SendError};
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_broadcast() {
+        let (sender, mut r1) = Broadcast::<usize>::new(4);
+        let r2 = sender.subscribe();
+        let mut handles = vec![];
+        for mut r in [r1.clone(), r2.clone()] {
+            handles.push(tokio::spawn(async move {
+                let mut list = vec![];
+                while let Ok(i) = r.recv().await {
+                    list.push(i);
+                }
+                list
+            }));
+        }
+        for i in 0..3 {
+            sender.send(i).unwrap();
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        sender.close();
+        assert_eq!(sender.send(3), Err(SendError::CLOSED(3)));
+        for h in handles {
+            assert_eq!(h.await.unwrap(), vec![0, 1, 2]);
+        }
+        assert_eq!(r1.try_recv(), Ok(0));
+        assert_eq!(r2.len(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_broadcast_lagged() {
+        let (sender, mut receiver) = Broadcast::<usize>::new(2);
+        for i in 0..5 {
+            sender.send(i).unwrap();
+        }
+        let mut late = sender.subscribe();
+        assert_eq!(receiver.try_recv(), Err(RecvError::LAGGED(3)));
+        assert_eq!(receiver.recv().await, Ok(3));
+        assert_eq!(receiver.recv().await, Ok(4));
+        assert_eq!(receiver.try_recv(), Err(RecvError::EMPTY));
+        assert_eq!(late.try_recv(), Err(RecvError::EMPTY));
+        sender.send(5).unwrap();
+        assert_eq!(late.recv().await, Ok(5));
+    }
+}
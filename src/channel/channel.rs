@@ -1,32 +1,62 @@
+use crate::channel::ring::{RingBuffer, Waiters};
 use crate::channel::*;
 use pin_project_lite::pin_project;
-use std::collections::VecDeque;
 use std::future::Future;
-use std::ops::DerefMut;
 use std::pin::Pin;
-use std::sync::atomic::{AtomicBool, Ordering};
-use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicU8, AtomicUsize, Ordering};
+use std::sync::Arc;
 use std::task::{Context, Poll, Waker};
+use std::time::{Duration, Instant};
+use tokio::time::Sleep;
+
+// how many times a parked receiver spins on an item a sender is still writing
+const PARKED_SPIN: usize = 64;
 
 #[derive(Debug)]
 pub struct Channel<T> {
-    status: Arc<AtomicBool>,
-    cap: usize,
-    wait_deque: Arc<Mutex<WaitDeque<T>>>,
+    shared: Arc<Shared<T>>,
 }
 
+// the lock-free queue and the parked wakers of both sides,
+// kept behind one Arc so cloning a Channel for every send/recv is cheap
 #[derive(Debug)]
-pub struct WaitDeque<T> {
-    deque: VecDeque<T>,
-    sender_waker: VecDeque<Waker>,
-    receiver_waker: VecDeque<Waker>,
+struct Shared<T> {
+    status: AtomicBool,
+    // usize::MAX means unbounded
+    cap: AtomicUsize,
+    // the number of live Sender/Receiver handles
+    senders: AtomicUsize,
+    receivers: AtomicUsize,
+    queue: RingBuffer<T>,
+    sender_waker: Waiters,
+    receiver_waker: Waiters,
+    policy: AtomicU8,
+    dropped: AtomicU64,
+    rejected: AtomicU64,
+}
+
+fn policy_from_u8(n: u8) -> OverflowPolicy {
+    match n {
+        1 => OverflowPolicy::DropNewest,
+        2 => OverflowPolicy::DropOldest,
+        3 => OverflowPolicy::Reject,
+        _ => OverflowPolicy::Block,
+    }
+}
+
+fn policy_to_u8(policy: OverflowPolicy) -> u8 {
+    match policy {
+        OverflowPolicy::Block => 0,
+        OverflowPolicy::DropNewest => 1,
+        OverflowPolicy::DropOldest => 2,
+        OverflowPolicy::Reject => 3,
+    }
 }
+
 impl<T> Clone for Channel<T> {
     fn clone(&self) -> Self {
         Self {
-            status: self.status.clone(),
-            cap: self.cap,
-            wait_deque: self.wait_deque.clone(),
+            shared: self.shared.clone(),
         }
     }
 }
@@ -36,33 +66,51 @@ unsafe impl<T> Sync for Channel<T> {}
 
 pin_project! {
     pub struct SendFuture<T>{
-    data: Option<T>,
-    chan: Channel<T>,
+        data: Option<T>,
+        chan: Channel<T>,
+        #[pin]
+        sleep: Option<Sleep>,
+        // the waker registered in sender_waker, removed when the future is dropped
+        waker: Option<Waker>,
+    }
+    impl<T> PinnedDrop for SendFuture<T> {
+        fn drop(this: Pin<&mut Self>) {
+            let this = this.project();
+            if let Some(w) = this.waker.take() {
+                this.chan.cancel_send_waker(&w);
+            }
+        }
     }
 }
 impl<T> Future for SendFuture<T> {
     type Output = ChannelResult<(), SendError<T>>;
 
     fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
-        let mut this = self.project();
-        let data = this.data.deref_mut();
-        let cap = this.chan.cap;
-        let res = this.chan._try_send(data, |c, d| {
-            if c.deque.len() >= cap {
-                c.sender_waker.push_back(cx.waker().clone());
-                Ok(Poll::Pending)
-            } else {
-                let data = d.take().unwrap();
-                c.deque.push_back(data);
-                if let Some(recv) = c.receiver_waker.pop_front() {
-                    recv.wake();
+        let this = self.project();
+        match this.chan.poll_send(this.data, cx.waker()) {
+            Ok(true) => {
+                // the waker may still be parked if the poll was not caused by a recv
+                if let Some(w) = this.waker.take() {
+                    this.chan.shared.sender_waker.remove(&w);
+                }
+                Poll::Ready(Ok(()))
+            }
+            Ok(false) => {
+                if let Some(sleep) = this.sleep.as_pin_mut() {
+                    if sleep.poll(cx).is_ready() {
+                        this.chan.cancel_send_waker(cx.waker());
+                        *this.waker = None;
+                        let data = this.data.take().unwrap();
+                        return Poll::Ready(SendError::TIMEOUT(data).into_err());
+                    }
                 }
-                Ok(Poll::Ready(ChannelResult::Ok(())))
+                *this.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+            Err(e) => {
+                *this.waker = None;
+                Poll::Ready(Err(e))
             }
-        });
-        match res {
-            Ok(o) => o,
-            Err(e) => Poll::Ready(ChannelResult::Err(e)),
         }
     }
 }
@@ -70,6 +118,24 @@ impl<T> Future for SendFuture<T> {
 pin_project! {
     pub struct RecvFuture<T>{
         chan: Channel<T>,
+        #[pin]
+        sleep: Option<Sleep>,
+        // the waker registered in receiver_waker, removed when the future is dropped
+        waker: Option<Waker>,
+    }
+    impl<T> PinnedDrop for RecvFuture<T> {
+        fn drop(this: Pin<&mut Self>) {
+            let this = this.project();
+            if let Some(w) = this.waker.take() {
+                this.chan.cancel_recv_waker(&w);
+            }
+        }
+    }
+}
+
+impl<T> RecvFuture<T> {
+    pub(crate) fn into_channel(self) -> Channel<T> {
+        self.chan.clone()
     }
 }
 
@@ -78,15 +144,24 @@ impl<T> Future for RecvFuture<T> {
 
     fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
         let this = self.project();
-        let res = this.chan._try_recv(|c| {
-            c.receiver_waker.push_back(cx.waker().clone());
-        });
-        match res {
-            Ok(t) => Poll::Ready(ChannelResult::Ok(t)),
-            Err(e) => match e {
-                RecvError::EMPTY => Poll::Pending,
-                _ => Poll::Ready(e.into_err()),
-            },
+        match this.chan._try_recv(Some(cx.waker())) {
+            Err(RecvError::EMPTY) => {
+                if let Some(sleep) = this.sleep.as_pin_mut() {
+                    if sleep.poll(cx).is_ready() {
+                        this.chan.cancel_recv_waker(cx.waker());
+                        *this.waker = None;
+                        return Poll::Ready(RecvError::TIMEOUT.into_err());
+                    }
+                }
+                *this.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+            res => {
+                if let Some(w) = this.waker.take() {
+                    this.chan.shared.receiver_waker.remove(&w);
+                }
+                Poll::Ready(res)
+            }
         }
     }
 }
@@ -96,98 +171,354 @@ impl<T> Channel<T> {
         if cap <= 0 {
             cap = 1;
         }
-        let deque = VecDeque::with_capacity(cap);
+        Self::with_queue(cap)
+    }
+    // send never waits on an unbounded channel
+    pub fn with_unbounded() -> Channel<T> {
+        Self::with_queue(usize::MAX)
+    }
+    fn with_queue(cap: usize) -> Channel<T> {
         Channel {
-            status: Arc::new(AtomicBool::new(true)),
-            cap,
-            wait_deque: Arc::new(Mutex::new(WaitDeque {
-                deque,
-                sender_waker: VecDeque::new(),
-                receiver_waker: VecDeque::new(),
-            })),
+            shared: Arc::new(Shared {
+                status: AtomicBool::new(true),
+                cap: AtomicUsize::new(cap),
+                senders: AtomicUsize::new(0),
+                receivers: AtomicUsize::new(0),
+                queue: RingBuffer::new(cap),
+                sender_waker: Waiters::default(),
+                receiver_waker: Waiters::default(),
+                policy: AtomicU8::new(policy_to_u8(OverflowPolicy::Block)),
+                dropped: AtomicU64::new(0),
+                rejected: AtomicU64::new(0),
+            }),
+        }
+    }
+    pub fn capacity(&self) -> usize {
+        self.shared.cap.load(Ordering::SeqCst)
+    }
+    pub fn is_unbounded(&self) -> bool {
+        self.capacity() == usize::MAX
+    }
+    // change the capacity at runtime, blocked senders are woken up if it grows,
+    // if it shrinks below the current length, send waits until the queue drains
+    pub fn set_capacity(&self, cap: usize) {
+        let cap = cap.max(1);
+        let old = self.shared.cap.swap(cap, Ordering::SeqCst);
+        if cap > old {
+            let room = cap.saturating_sub(self.shared.queue.len());
+            self.shared.sender_waker.wake(room);
+        }
+    }
+    pub fn set_unbounded(&self) {
+        self.set_capacity(usize::MAX)
+    }
+    pub fn overflow_policy(&self) -> OverflowPolicy {
+        policy_from_u8(self.shared.policy.load(Ordering::SeqCst))
+    }
+    // decide what send does when the channel is full,
+    // under DropOldest send and try_send drop the evicted item, try_send_evict returns it
+    pub fn set_overflow_policy(&self, policy: OverflowPolicy) {
+        self.shared
+            .policy
+            .store(policy_to_u8(policy), Ordering::SeqCst);
+        // senders blocked before can not wait anymore
+        if policy != OverflowPolicy::Block {
+            self.shared.sender_waker.wake_all();
+        }
+    }
+    pub fn stats(&self) -> ChannelStats {
+        ChannelStats {
+            len: self.shared.queue.len(),
+            dropped: self.shared.dropped.load(Ordering::Relaxed),
+            rejected: self.shared.rejected.load(Ordering::Relaxed),
         }
     }
     pub fn get_status(&self) -> bool {
-        self.status.load(Ordering::Relaxed)
+        self.shared.status.load(Ordering::SeqCst)
     }
-    pub(crate) fn _try_send<Out>(
-        &self,
-        data: &mut Option<T>,
-        send_handle: impl FnOnce(&mut WaitDeque<T>, &mut Option<T>) -> ChannelResult<Out, SendError<T>>,
-    ) -> ChannelResult<Out, SendError<T>> {
+    // push without waiting, the overflow policy is applied if the channel is full
+    fn push(&self, data: T) -> ChannelResult<Option<T>, SendError<T>> {
         if !self.get_status() {
-            let data = data.take().unwrap();
             return SendError::CLOSED(data).into_err();
         }
-        let mut lock = match self.wait_deque.lock() {
-            Ok(o) => o,
-            Err(e) => {
-                let data = data.take().unwrap();
-                return SendError::UNKNOWN(data, e.to_string()).into_err();
-            }
+        let cap = self.capacity();
+        let data = match self.shared.queue.push(data, cap) {
+            Ok(_) => return self.sent(None),
+            Err(d) => d,
         };
-        send_handle(lock.deref_mut(), data)
-    }
-    pub fn try_send(&self, data: T) -> ChannelResult<(), SendError<T>> {
-        let mut data = Some(data);
-        let cap = self.cap;
-        self._try_send(&mut data, |c, d| {
-            let data = d.take().unwrap();
-            if c.deque.len() >= cap {
+        match self.overflow_policy() {
+            OverflowPolicy::Block => SendError::FULL(data).into_err(),
+            OverflowPolicy::Reject => {
+                self.shared.rejected.fetch_add(1, Ordering::Relaxed);
                 SendError::FULL(data).into_err()
-            } else {
-                c.deque.push_back(data);
-                if let Some(recv) = c.receiver_waker.pop_front() {
-                    recv.wake();
-                }
-                Ok(())
             }
-        })
+            OverflowPolicy::DropNewest => {
+                self.shared.dropped.fetch_add(1, Ordering::Relaxed);
+                Ok(None)
+            }
+            OverflowPolicy::DropOldest => self.push_evict(data, cap),
+        }
+    }
+    // evict the head to make room, only the last evicted item is given back
+    // if other senders take the room first
+    fn push_evict(&self, mut data: T, cap: usize) -> ChannelResult<Option<T>, SendError<T>> {
+        let mut evicted = None;
+        loop {
+            if let Some(e) = self.shared.queue.pop() {
+                self.shared.dropped.fetch_add(1, Ordering::Relaxed);
+                evicted = Some(e);
+            }
+            match self.shared.queue.push(data, cap) {
+                Ok(_) => return self.sent(evicted),
+                Err(d) => data = d,
+            }
+        }
+    }
+    // the push and the pop in the queue are SeqCst, so either the other side sees the item or room
+    // after parking its waker, or the waker is seen here
+    fn sent(&self, evicted: Option<T>) -> ChannelResult<Option<T>, SendError<T>> {
+        self.shared.receiver_waker.wake_one();
+        Ok(evicted)
+    }
+    fn received(&self, t: T) -> ChannelResult<T, RecvError> {
+        self.shared.sender_waker.wake_one();
+        Ok(t)
+    }
+    // the head evicted by OverflowPolicy::DropOldest is dropped
+    pub fn try_send(&self, data: T) -> ChannelResult<(), SendError<T>> {
+        self.try_send_evict(data).map(|_| ())
+    }
+    // same as try_send, but give back the head evicted by OverflowPolicy::DropOldest,
+    // the only way to get the evicted item, send and try_send drop it
+    pub fn try_send_evict(&self, data: T) -> ChannelResult<Option<T>, SendError<T>> {
+        self.push(data)
     }
     pub fn send(&self, value: T) -> SendFuture<T> {
+        self.send_with_sleep(value, None)
+    }
+    // the value is given back by SendError::TIMEOUT if it can not be sent in time
+    pub fn send_timeout(&self, value: T, timeout: Duration) -> SendFuture<T> {
+        self.send_with_sleep(value, Some(tokio::time::sleep(timeout)))
+    }
+    pub fn send_deadline(&self, value: T, deadline: Instant) -> SendFuture<T> {
+        let deadline = tokio::time::Instant::from_std(deadline);
+        self.send_with_sleep(value, Some(tokio::time::sleep_until(deadline)))
+    }
+    fn send_with_sleep(&self, value: T, sleep: Option<Sleep>) -> SendFuture<T> {
         SendFuture {
             data: Some(value),
             chan: self.clone(),
+            sleep,
+            waker: None,
+        }
+    }
+    // take an item, if it is empty and waker is given, park the waker and try again
+    // so an item sent in between is not missed
+    pub(crate) fn _try_recv(&self, waker: Option<&Waker>) -> ChannelResult<T, RecvError> {
+        if let Some(t) = self.shared.queue.pop() {
+            return self.received(t);
+        }
+        if !self.get_status() {
+            // the items sent before close are still received
+            return match self.shared.queue.pop() {
+                Some(t) => self.received(t),
+                None => RecvError::CLOSED.into_err(),
+            };
+        }
+        let w = match waker {
+            Some(w) => w,
+            None => return RecvError::EMPTY.into_err(),
+        };
+        self.shared.receiver_waker.register(w);
+        let closed = !self.get_status();
+        match self.pop_parked(w) {
+            Some(t) => {
+                self.shared.receiver_waker.remove(w);
+                self.received(t)
+            }
+            None if closed && self.shared.queue.is_empty() => {
+                self.shared.receiver_waker.remove(w);
+                RecvError::CLOSED.into_err()
+            }
+            None => RecvError::EMPTY.into_err(),
+        }
+    }
+    // pop after the waker is parked, a sender may have taken a slot before seeing the waker,
+    // so spin a little for it to write the item, then wake the waker to be polled again
+    fn pop_parked(&self, waker: &Waker) -> Option<T> {
+        for _ in 0..PARKED_SPIN {
+            if let Some(t) = self.shared.queue.pop() {
+                return Some(t);
+            }
+            if self.shared.queue.is_empty() {
+                return None;
+            }
+            std::hint::spin_loop();
+        }
+        waker.wake_by_ref();
+        None
+    }
+    // move up to max - buf.len() items into buf,
+    // park the waker if buf is still not full, return whether the channel is closed
+    pub(crate) fn _try_recv_many(
+        &self,
+        buf: &mut Vec<T>,
+        max: usize,
+        waker: Option<&Waker>,
+    ) -> ChannelResult<bool, RecvError> {
+        let mut closed = self.drain_into(buf, max);
+        if let Some(w) = waker {
+            if buf.len() < max && !closed {
+                self.shared.receiver_waker.register(w);
+                let mut spin = 0;
+                loop {
+                    closed = self.drain_into(buf, max);
+                    if buf.len() >= max || closed || self.shared.queue.is_empty() {
+                        break;
+                    }
+                    // a sender is still writing, see pop_parked
+                    spin += 1;
+                    if spin >= PARKED_SPIN {
+                        w.wake_by_ref();
+                        break;
+                    }
+                    std::hint::spin_loop();
+                }
+                if buf.len() >= max || closed {
+                    self.shared.receiver_waker.remove(w);
+                }
+            }
+        }
+        Ok(closed)
+    }
+    // return whether the channel is closed and empty
+    fn drain_into(&self, buf: &mut Vec<T>, max: usize) -> bool {
+        let closed = !self.get_status();
+        let mut n = 0;
+        while buf.len() < max {
+            match self.shared.queue.pop() {
+                Some(t) => buf.push(t),
+                None => break,
+            }
+            n += 1;
+        }
+        if n > 0 {
+            self.shared.sender_waker.wake(n);
         }
+        closed && self.shared.queue.is_empty()
     }
-    pub(crate) fn _try_recv(
+    // send the data if there is room, otherwise park the waker once and return false
+    pub(crate) fn poll_send(
         &self,
-        empty: impl FnOnce(&mut WaitDeque<T>),
-    ) -> ChannelResult<T, RecvError> {
-        let mut lock = match self.wait_deque.lock() {
-            Ok(o) => o,
-            Err(e) => return RecvError::UNKNOWN(e.to_string()).into_err(),
+        data: &mut Option<T>,
+        waker: &Waker,
+    ) -> ChannelResult<bool, SendError<T>> {
+        let value = data.take().unwrap();
+        let value = match self.push(value) {
+            Ok(_) => return Ok(true),
+            Err(SendError::FULL(v)) if self.overflow_policy() == OverflowPolicy::Block => v,
+            Err(e) => return Err(e),
         };
-        if let Some(s) = lock.deque.pop_front() {
-            if let Some(w) = lock.sender_waker.pop_front() {
-                w.wake();
+        self.shared.sender_waker.register(waker);
+        match self.push(value) {
+            Ok(_) => {
+                self.shared.sender_waker.remove(waker);
+                Ok(true)
+            }
+            Err(SendError::FULL(v)) if self.overflow_policy() == OverflowPolicy::Block => {
+                *data = Some(v);
+                Ok(false)
             }
-            return Ok(s);
-        } else {
-            if !self.get_status() {
-                return RecvError::CLOSED.into_err();
+            Err(e) => {
+                self.shared.sender_waker.remove(waker);
+                Err(e)
             }
-            empty(lock.deref_mut());
         }
-        RecvError::EMPTY.into_err()
+    }
+    // remove the waker of a cancelled sender,
+    // if it has been woken but nothing was sent, pass the notification on to the next sender
+    pub(crate) fn cancel_send_waker(&self, waker: &Waker) {
+        if !self.shared.sender_waker.remove(waker) && self.shared.queue.len() < self.capacity() {
+            self.shared.sender_waker.wake_one();
+        }
+    }
+    // remove the waker of a cancelled receiver,
+    // if it has been woken but the item was not taken, pass the notification on to the next receiver
+    pub(crate) fn cancel_recv_waker(&self, waker: &Waker) {
+        if !self.shared.receiver_waker.remove(waker) && !self.shared.queue.is_empty() {
+            self.shared.receiver_waker.wake_one();
+        }
+    }
+    // remove a parked waker whose wait is over
+    pub(crate) fn remove_send_waker(&self, waker: &Waker) {
+        self.shared.sender_waker.remove(waker);
+    }
+    pub(crate) fn remove_recv_waker(&self, waker: &Waker) {
+        self.shared.receiver_waker.remove(waker);
     }
     pub fn try_recv(&self) -> ChannelResult<T, RecvError> {
-        self._try_recv(|_e| {})
+        self._try_recv(None)
     }
     pub fn recv(&self) -> RecvFuture<T> {
-        RecvFuture { chan: self.clone() }
+        self.recv_with_sleep(None)
     }
-    pub fn close(&self) {
-        self.status.store(false, Ordering::Relaxed);
-        let mut lock = self.wait_deque.lock().unwrap();
-        for i in lock.receiver_waker.drain(..) {
-            i.wake();
-        }
-        for i in lock.sender_waker.drain(..) {
-            i.wake();
+    pub fn recv_timeout(&self, timeout: Duration) -> RecvFuture<T> {
+        self.recv_with_sleep(Some(tokio::time::sleep(timeout)))
+    }
+    pub fn recv_deadline(&self, deadline: Instant) -> RecvFuture<T> {
+        let deadline = tokio::time::Instant::from_std(deadline);
+        self.recv_with_sleep(Some(tokio::time::sleep_until(deadline)))
+    }
+    fn recv_with_sleep(&self, sleep: Option<Sleep>) -> RecvFuture<T> {
+        RecvFuture {
+            chan: self.clone(),
+            sleep,
+            waker: None,
         }
     }
+    pub fn close(&self) {
+        self.close_send()
+    }
+    // stop sending, receivers can still take the remaining items
+    pub fn close_send(&self) {
+        self.shared.status.store(false, Ordering::SeqCst);
+        self.wake_all();
+    }
+    // stop both sending and receiving, the remaining items are dropped
+    pub fn close_recv(&self) {
+        self.shared.status.store(false, Ordering::SeqCst);
+        while self.shared.queue.pop().is_some() {}
+        self.wake_all();
+    }
+    fn wake_all(&self) {
+        self.shared.receiver_waker.wake_all();
+        self.shared.sender_waker.wake_all();
+    }
     pub fn is_closed(&self) -> bool {
-        !self.status.load(Ordering::Relaxed)
+        !self.get_status()
+    }
+    pub fn sender_count(&self) -> usize {
+        self.shared.senders.load(Ordering::Relaxed)
+    }
+    pub fn receiver_count(&self) -> usize {
+        self.shared.receivers.load(Ordering::Relaxed)
+    }
+    pub(crate) fn add_sender(&self) {
+        self.shared.senders.fetch_add(1, Ordering::Relaxed);
+    }
+    // the last sender closes the channel for sending
+    pub(crate) fn remove_sender(&self) {
+        if self.shared.senders.fetch_sub(1, Ordering::AcqRel) == 1 {
+            self.close_send();
+        }
+    }
+    pub(crate) fn add_receiver(&self) {
+        self.shared.receivers.fetch_add(1, Ordering::Relaxed);
+    }
+    // the last receiver closes the channel, send returns CLOSED
+    pub(crate) fn remove_receiver(&self) {
+        if self.shared.receivers.fetch_sub(1, Ordering::AcqRel) == 1 {
+            self.close_recv();
+        }
     }
 }
@@ -1,22 +1,56 @@
-use crate::channel::{Channel, ChannelResult, RecvError, RecvFuture, SendError, SendFuture};
+use crate::channel::{
+    Channel, ChannelError, ChannelResult, ChannelStats, OverflowPolicy, RecvError, RecvFuture,
+    SendError, SendFuture,
+};
 use std::sync::Arc;
+use std::task::{Context, Poll, Waker};
+use std::time::{Duration, Instant};
 
 #[derive(Debug)]
 pub struct Sender<T> {
     chan: Arc<Channel<T>>,
+    // the item accepted by Sink::start_send but not sent yet
+    pending: Option<T>,
+    // the waker parked by the Sink, removed once the item is sent or the sender is dropped
+    waker: Option<Waker>,
+    // Sink::poll_close has given up this handle
+    released: bool,
 }
 
+// the pending item is never pinned
+impl<T> Unpin for Sender<T> {}
+
 impl<T> Clone for Sender<T> {
     fn clone(&self) -> Self {
-        Self {
-            chan: self.chan.clone(),
-        }
+        Self::from(self.chan.clone())
     }
 }
 
 impl<T> From<Arc<Channel<T>>> for Sender<T> {
     fn from(chan: Arc<Channel<T>>) -> Self {
-        Sender { chan }
+        chan.add_sender();
+        Sender {
+            chan,
+            pending: None,
+            waker: None,
+            released: false,
+        }
+    }
+}
+
+// dropping the last sender closes the channel for sending,
+// an item left by Sink::start_send is sent if there is room, otherwise it is dropped
+impl<T> Drop for Sender<T> {
+    fn drop(&mut self) {
+        if let Some(w) = self.waker.take() {
+            self.chan.cancel_send_waker(&w);
+        }
+        if let Some(t) = self.pending.take() {
+            let _ = self.chan.try_send(t);
+        }
+        if !self.released {
+            self.chan.remove_sender();
+        }
     }
 }
 
@@ -24,33 +58,118 @@ impl<T> Sender<T> {
     pub fn try_send(&self, data: T) -> ChannelResult<(), SendError<T>> {
         self.chan.try_send(data)
     }
+    pub fn try_send_evict(&self, data: T) -> ChannelResult<Option<T>, SendError<T>> {
+        self.chan.try_send_evict(data)
+    }
     pub fn send(&self, value: T) -> SendFuture<T> {
         self.chan.send(value)
     }
+    pub fn send_timeout(&self, value: T, timeout: Duration) -> SendFuture<T> {
+        self.chan.send_timeout(value, timeout)
+    }
+    pub fn send_deadline(&self, value: T, deadline: Instant) -> SendFuture<T> {
+        self.chan.send_deadline(value, deadline)
+    }
     pub fn close(&self) {
         self.chan.close()
     }
+    pub fn close_send(&self) {
+        self.chan.close_send()
+    }
+    pub fn close_recv(&self) {
+        self.chan.close_recv()
+    }
     pub fn is_closed(&self) -> bool {
         self.chan.is_closed()
     }
+    pub fn capacity(&self) -> usize {
+        self.chan.capacity()
+    }
+    pub fn set_capacity(&self, cap: usize) {
+        self.chan.set_capacity(cap)
+    }
+    pub fn set_overflow_policy(&self, policy: OverflowPolicy) {
+        self.chan.set_overflow_policy(policy)
+    }
+    pub fn stats(&self) -> ChannelStats {
+        self.chan.stats()
+    }
+    pub(crate) fn channel(&self) -> &Channel<T> {
+        &self.chan
+    }
+    pub(crate) fn set_pending(&mut self, item: T) -> ChannelResult<(), SendError<Option<T>>> {
+        if self.released {
+            return SendError::CLOSED(Some(item)).into_err();
+        }
+        if self.pending.is_some() {
+            return SendError::FULL(Some(item)).into_err();
+        }
+        self.pending = Some(item);
+        Ok(())
+    }
+    // send the pending item, wait if the channel is full
+    pub(crate) fn poll_send_pending(
+        &mut self,
+        cx: &mut Context<'_>,
+    ) -> Poll<ChannelResult<(), SendError<Option<T>>>> {
+        if self.pending.is_none() {
+            return Poll::Ready(Ok(()));
+        }
+        let res = self.chan.poll_send(&mut self.pending, cx.waker());
+        if let Ok(false) = res {
+            self.waker = Some(cx.waker().clone());
+            return Poll::Pending;
+        }
+        if let Some(w) = self.waker.take() {
+            self.chan.remove_send_waker(&w);
+        }
+        match res {
+            Ok(_) => Poll::Ready(Ok(())),
+            Err(SendError::CLOSED(t)) => Poll::Ready(Err(SendError::CLOSED(Some(t)))),
+            Err(SendError::FULL(t)) => Poll::Ready(Err(SendError::FULL(Some(t)))),
+            Err(SendError::TIMEOUT(t)) => Poll::Ready(Err(SendError::TIMEOUT(Some(t)))),
+            Err(SendError::UNKNOWN(t, e)) => Poll::Ready(Err(SendError::UNKNOWN(Some(t), e))),
+        }
+    }
+    // give up this handle, the channel is closed for sending if it is the last sender
+    pub(crate) fn release(&mut self) {
+        if !self.released {
+            self.released = true;
+            self.chan.remove_sender();
+        }
+    }
+    pub(crate) fn is_released(&self) -> bool {
+        self.released
+    }
 }
 
 #[derive(Debug)]
 pub struct Receiver<T> {
     chan: Arc<Channel<T>>,
+    // the waker parked by Stream::poll_next, removed once an item is taken or the receiver is dropped
+    waker: Option<Waker>,
 }
 
 impl<T> Clone for Receiver<T> {
     fn clone(&self) -> Self {
-        Self {
-            chan: self.chan.clone(),
-        }
+        Self::from(self.chan.clone())
     }
 }
 
 impl<T> From<Arc<Channel<T>>> for Receiver<T> {
     fn from(chan: Arc<Channel<T>>) -> Self {
-        Receiver { chan }
+        chan.add_receiver();
+        Receiver { chan, waker: None }
+    }
+}
+
+// dropping the last receiver closes the channel, send returns CLOSED
+impl<T> Drop for Receiver<T> {
+    fn drop(&mut self) {
+        if let Some(w) = self.waker.take() {
+            self.chan.cancel_recv_waker(&w);
+        }
+        self.chan.remove_receiver();
     }
 }
 
@@ -61,12 +180,49 @@ impl<T> Receiver<T> {
     pub fn recv(&self) -> RecvFuture<T> {
         self.chan.recv()
     }
+    pub fn recv_timeout(&self, timeout: Duration) -> RecvFuture<T> {
+        self.chan.recv_timeout(timeout)
+    }
+    pub fn recv_deadline(&self, deadline: Instant) -> RecvFuture<T> {
+        self.chan.recv_deadline(deadline)
+    }
     pub fn close(&self) {
         self.chan.close()
     }
+    pub fn close_send(&self) {
+        self.chan.close_send()
+    }
+    pub fn close_recv(&self) {
+        self.chan.close_recv()
+    }
     pub fn is_closed(&self) -> bool {
         self.chan.is_closed()
     }
+    pub fn capacity(&self) -> usize {
+        self.chan.capacity()
+    }
+    pub fn set_capacity(&self, cap: usize) {
+        self.chan.set_capacity(cap)
+    }
+    pub fn set_overflow_policy(&self, policy: OverflowPolicy) {
+        self.chan.set_overflow_policy(policy)
+    }
+    pub fn stats(&self) -> ChannelStats {
+        self.chan.stats()
+    }
+    pub(crate) fn channel(&self) -> &Channel<T> {
+        &self.chan
+    }
+    // take an item for Stream::poll_next, the waker stays parked only while it returns EMPTY
+    pub(crate) fn poll_recv(&mut self, cx: &mut Context<'_>) -> ChannelResult<T, RecvError> {
+        let res = self.chan._try_recv(Some(cx.waker()));
+        if let Err(RecvError::EMPTY) = res {
+            self.waker = Some(cx.waker().clone());
+        } else if let Some(w) = self.waker.take() {
+            self.chan.remove_recv_waker(&w);
+        }
+        res
+    }
 }
 
 impl<T> Channel<T> {
@@ -74,4 +230,8 @@ impl<T> Channel<T> {
         let chan = Arc::new(Channel::with_cap(cap));
         (Sender::from(chan.clone()), Receiver::from(chan))
     }
+    pub fn unbounded() -> (Sender<T>, Receiver<T>) {
+        let chan = Arc::new(Channel::with_unbounded());
+        (Sender::from(chan.clone()), Receiver::from(chan))
+    }
 }
@@ -13,6 +13,7 @@ pub trait ChannelError: Display {
 pub enum SendError<T> {
     CLOSED(T),
     FULL(T),
+    TIMEOUT(T),
     UNKNOWN(T, String),
 }
 impl<T> Display for SendError<T> {
@@ -20,6 +21,7 @@ impl<T> Display for SendError<T> {
         match self {
             SendError::CLOSED(_) => write!(f, "ChannelClose"),
             SendError::FULL(_) => write!(f, "ChannelFull"),
+            SendError::TIMEOUT(_) => write!(f, "ChannelTimeout"),
             SendError::UNKNOWN(_, e) => write!(f, "ChannelUnknown error:{e}"),
         }
     }
@@ -37,6 +39,11 @@ impl<T> std::error::Error for SendError<T> {}
 pub enum RecvError {
     CLOSED,
     EMPTY,
+    // the receiver is too slow, n messages were overwritten
+    LAGGED(u64),
+    TIMEOUT,
+    // the record of the sequence can not be decoded, it is skipped and can be acked
+    DECODE(u64, String),
     UNKNOWN(String),
 }
 impl Display for RecvError {
@@ -44,6 +51,9 @@ impl Display for RecvError {
         match self {
             RecvError::CLOSED => write!(f, "ChannelClose"),
             RecvError::EMPTY => write!(f, "ChannelEmpty"),
+            RecvError::LAGGED(n) => write!(f, "ChannelLagged({n})"),
+            RecvError::TIMEOUT => write!(f, "ChannelTimeout"),
+            RecvError::DECODE(seq, e) => write!(f, "ChannelDecode({seq}) error:{e}"),
             RecvError::UNKNOWN(e) => write!(f, "ChannelUnknown error:{e}"),
         }
     }
@@ -1,15 +1,39 @@
+mod batch;
+mod blocking;
+mod broadcast;
 mod channel;
 mod channel_split;
 mod error;
+mod oneshot;
+mod overflow;
+#[cfg(feature = "persist")]
+mod persist;
+mod pipeline;
+mod priority;
+mod ring;
+mod select;
+mod stream;
+mod watch;
 
+pub use batch::*;
+pub use broadcast::*;
 pub use channel::*;
 pub use channel_split::*;
 pub use error::*;
+pub use oneshot::*;
+pub use overflow::*;
+#[cfg(feature = "persist")]
+pub use persist::*;
+pub use pipeline::*;
+pub use priority::*;
+pub use select::*;
+pub use watch::*;
 
 #[cfg(test)]
 mod test {
     use super::*;
     use crate::sync::WaitGroup;
+    use std::time::{Duration, Instant};
 
     #[tokio::test]
     async fn test_channel() {
@@ -26,6 +50,96 @@ mod test {
         assert_eq!(res, Err(RecvError::EMPTY), "first try recv failed");
     }
 
+    #[tokio::test]
+    async fn test_channel_timeout() {
+        let (sender, receiver) = Channel::<usize>::new(1);
+        let res = receiver.recv_timeout(Duration::from_millis(10)).await;
+        assert_eq!(res, Err(RecvError::TIMEOUT));
+
+        sender.send(1).await.unwrap();
+        let deadline = Instant::now() + Duration::from_millis(10);
+        let res = sender.send_deadline(2, deadline).await;
+        assert_eq!(
+            res,
+            Err(SendError::TIMEOUT(2)),
+            "the value should be given back"
+        );
+
+        // a cancelled send must not leave its waker behind
+        let res = tokio::time::timeout(Duration::from_millis(10), sender.send(3)).await;
+        assert!(res.is_err());
+        let waiting = sender.clone();
+        let handle = tokio::spawn(async move { waiting.send(4).await });
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        assert_eq!(receiver.recv().await, Ok(1));
+        handle.await.unwrap().unwrap();
+        assert_eq!(
+            receiver.recv_timeout(Duration::from_millis(10)).await,
+            Ok(4)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_channel_capacity() {
+        let (sender, receiver) = Channel::<usize>::unbounded();
+        for i in 0..10_000 {
+            sender.try_send(i).unwrap();
+        }
+        assert_eq!(receiver.capacity(), usize::MAX);
+
+        let (sender, receiver) = Channel::<usize>::new(1);
+        sender.try_send(0).unwrap();
+        let waiting = sender.clone();
+        let handle = tokio::spawn(async move { waiting.send(1).await });
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        assert!(!handle.is_finished());
+        sender.set_capacity(2);
+        handle.await.unwrap().unwrap();
+
+        receiver.set_capacity(1);
+        assert_eq!(sender.try_send(2), Err(SendError::FULL(2)));
+        assert_eq!(receiver.try_recv(), Ok(0));
+        assert_eq!(sender.try_send(2), Err(SendError::FULL(2)));
+        assert_eq!(receiver.try_recv(), Ok(1));
+        sender.try_send(2).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_channel_drop() {
+        let (sender, receiver) = Channel::<usize>::new(4);
+        let other = sender.clone();
+        assert_eq!(receiver.channel().sender_count(), 2);
+        let handle = tokio::spawn(async move {
+            let mut list = vec![];
+            while let Ok(i) = receiver.recv().await {
+                list.push(i);
+            }
+            list
+        });
+        sender.send(1).await.unwrap();
+        drop(sender);
+        other.send(2).await.unwrap();
+        drop(other);
+        assert_eq!(handle.await.unwrap(), vec![1, 2]);
+
+        let (sender, receiver) = Channel::<usize>::new(4);
+        sender.try_send(1).unwrap();
+        drop(receiver);
+        assert_eq!(sender.try_send(2), Err(SendError::CLOSED(2)));
+
+        let (sender, receiver) = Channel::<usize>::new(4);
+        sender.try_send(1).unwrap();
+        sender.close_send();
+        assert_eq!(receiver.try_recv(), Ok(1));
+        assert_eq!(receiver.try_recv(), Err(RecvError::CLOSED));
+
+        let (sender, receiver) = Channel::<usize>::new(4);
+        sender.try_send(1).unwrap();
+        receiver.close_recv();
+        assert_eq!(receiver.try_recv(), Err(RecvError::CLOSED));
+        assert_eq!(sender.try_send(2), Err(SendError::CLOSED(2)));
+    }
+
     #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
     async fn test_channel_wait() {
         let wg = WaitGroup::default();
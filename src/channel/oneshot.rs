@@ -0,0 +1,169 @@
+use crate::channel::{ChannelError, ChannelResult, RecvError, SendError};
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Waker};
+
+/// 单次通道，用于请求/应答
+/// 只能发送一个值，发送方未发送就drop时接收方得到CLOSED，接收方本身就是Future
+#[derive(Debug)]
+pub struct Oneshot<T> {
+    state: Mutex<OneshotState<T>>,
+}
+
+#[derive(Debug)]
+struct OneshotState<T> {
+    value: Option<T>,
+    waker: Option<Waker>,
+    // the sender is gone, sent or dropped
+    sent: bool,
+    // the receiver is dropped or closed
+    closed: bool,
+}
+
+#[derive(Debug)]
+pub struct OneshotSender<T> {
+    chan: Arc<Oneshot<T>>,
+}
+
+#[derive(Debug)]
+pub struct OneshotReceiver<T> {
+    chan: Arc<Oneshot<T>>,
+}
+
+impl<T> Oneshot<T> {
+    #[allow(clippy::new_ret_no_self)]
+    pub fn new() -> (OneshotSender<T>, OneshotReceiver<T>) {
+        let chan = Arc::new(Oneshot {
+            state: Mutex::new(OneshotState {
+                value: None,
+                waker: None,
+                sent: false,
+                closed: false,
+            }),
+        });
+        (
+            OneshotSender { chan: chan.clone() },
+            OneshotReceiver { chan },
+        )
+    }
+    fn finish(&self, value: Option<T>) -> ChannelResult<(), SendError<T>> {
+        let mut state = match self.state.lock() {
+            Ok(o) => o,
+            Err(e) => {
+                return match value {
+                    Some(v) => SendError::UNKNOWN(v, e.to_string()).into_err(),
+                    None => Ok(()),
+                }
+            }
+        };
+        state.sent = true;
+        if let Some(v) = value {
+            if state.closed {
+                return SendError::CLOSED(v).into_err();
+            }
+            state.value = Some(v);
+        }
+        if let Some(w) = state.waker.take() {
+            w.wake();
+        }
+        Ok(())
+    }
+    fn _try_recv(&self, waker: Option<&Waker>) -> ChannelResult<T, RecvError> {
+        let mut state = match self.state.lock() {
+            Ok(o) => o,
+            Err(e) => return RecvError::UNKNOWN(e.to_string()).into_err(),
+        };
+        if let Some(v) = state.value.take() {
+            return Ok(v);
+        }
+        if state.sent || state.closed {
+            return RecvError::CLOSED.into_err();
+        }
+        if let Some(w) = waker {
+            if !state
+                .waker
+                .as_ref()
+                .map(|i| i.will_wake(w))
+                .unwrap_or(false)
+            {
+                state.waker = Some(w.clone());
+            }
+        }
+        RecvError::EMPTY.into_err()
+    }
+    fn is_closed(&self) -> bool {
+        self.state.lock().map(|s| s.closed).unwrap_or(true)
+    }
+}
+
+impl<T> OneshotSender<T> {
+    // the value is given back if the receiver is gone
+    pub fn send(self, value: T) -> ChannelResult<(), SendError<T>> {
+        self.chan.finish(Some(value))
+    }
+    pub fn is_closed(&self) -> bool {
+        self.chan.is_closed()
+    }
+}
+
+impl<T> Drop for OneshotSender<T> {
+    fn drop(&mut self) {
+        let _ = self.chan.finish(None);
+    }
+}
+
+impl<T> OneshotReceiver<T> {
+    pub fn try_recv(&mut self) -> ChannelResult<T, RecvError> {
+        self.chan._try_recv(None)
+    }
+    // the sender can not send anymore, a value already sent can still be received
+    pub fn close(&mut self) {
+        if let Ok(mut state) = self.chan.state.lock() {
+            state.closed = true;
+        }
+    }
+}
+
+impl<T> Drop for OneshotReceiver<T> {
+    fn drop(&mut self) {
+        self.close()
+    }
+}
+
+impl<T> Future for OneshotReceiver<T> {
+    type Output = ChannelResult<T, RecvError>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        match self.chan._try_recv(Some(cx.waker())) {
+            Err(RecvError::EMPTY) => Poll::Pending,
+            res => Poll::Ready(res),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::channel::{Oneshot, RecvError, SendError};
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_oneshot() {
+        let (sender, receiver) = Oneshot::<usize>::new();
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+            sender.send(1).unwrap();
+        });
+        assert_eq!(receiver.await, Ok(1));
+
+        let (sender, mut receiver) = Oneshot::<usize>::new();
+        assert_eq!(receiver.try_recv(), Err(RecvError::EMPTY));
+        drop(sender);
+        assert_eq!(receiver.await, Err(RecvError::CLOSED));
+
+        let (sender, receiver) = Oneshot::<usize>::new();
+        drop(receiver);
+        assert!(sender.is_closed());
+        assert_eq!(sender.send(2), Err(SendError::CLOSED(2)));
+    }
+}
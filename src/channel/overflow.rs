@@ -0,0 +1,72 @@
+/// 通道满了之后send的处理策略
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum OverflowPolicy {
+    // wait until there is room, try_send returns FULL
+    #[default]
+    Block,
+    // drop the item being sent, send returns Ok
+    DropNewest,
+    // evict the head of the queue to make room, send and try_send drop the evicted item,
+    // use try_send_evict to get it back
+    DropOldest,
+    // return FULL at once, send never waits
+    Reject,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct ChannelStats {
+    // the number of items in the channel
+    pub len: usize,
+    // dropped by DropNewest/DropOldest
+    pub dropped: u64,
+    // returned as FULL by Reject
+    pub rejected: u64,
+}
+
+#[cfg(test)]
+mod test {
+    use crate::channel::{Channel, OverflowPolicy, SendError};
+
+    #[tokio::test]
+    async fn test_overflow_policy() {
+        let (sender, receiver) = Channel::<usize>::new(2);
+        sender.set_overflow_policy(OverflowPolicy::DropNewest);
+        for i in 0..4 {
+            sender.send(i).await.unwrap();
+        }
+        let mut buf = vec![];
+        assert_eq!(receiver.recv_many(&mut buf, 4).await, Ok(2));
+        assert_eq!(buf, vec![0, 1]);
+        assert_eq!(sender.stats().dropped, 2);
+
+        let (sender, receiver) = Channel::<usize>::new(2);
+        sender.set_overflow_policy(OverflowPolicy::DropOldest);
+        sender.try_send(0).unwrap();
+        sender.try_send(1).unwrap();
+        assert_eq!(sender.try_send_evict(2), Ok(Some(0)));
+        sender.send(3).await.unwrap();
+        let mut buf = vec![];
+        assert_eq!(receiver.recv_many(&mut buf, 4).await, Ok(2));
+        assert_eq!(buf, vec![2, 3]);
+        assert_eq!(receiver.stats().dropped, 2);
+
+        // send and try_send drop the evicted item, try_send_evict gives it back
+        let (sender, receiver) = Channel::<usize>::new(1);
+        sender.set_overflow_policy(OverflowPolicy::DropOldest);
+        sender.try_send(0).unwrap();
+        sender.try_send(1).unwrap();
+        sender.send(2).await.unwrap();
+        assert_eq!(sender.try_send_evict(3), Ok(Some(2)));
+        assert_eq!(sender.try_send_evict(4), Ok(Some(3)));
+        assert_eq!(receiver.try_recv(), Ok(4));
+        assert_eq!(sender.try_send_evict(5), Ok(None));
+        assert_eq!(receiver.stats().dropped, 4);
+
+        let (sender, receiver) = Channel::<usize>::new(1);
+        sender.set_overflow_policy(OverflowPolicy::Reject);
+        sender.try_send(0).unwrap();
+        assert_eq!(sender.send(1).await, Err(SendError::FULL(1)));
+        let stats = receiver.stats();
+        assert_eq!((stats.len, stats.rejected), (1, 1));
+    }
+}
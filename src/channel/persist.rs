@@ -0,0 +1,620 @@
+use crate::channel::{ChannelError, ChannelResult, RecvError, SendError};
+use std::collections::BTreeSet;
+use std::io::{ErrorKind, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use tokio::fs::{File, OpenOptions};
+use tokio::io::{AsyncReadExt, AsyncSeekExt, AsyncWriteExt};
+use tokio::sync::{Mutex, Notify};
+
+const SEGMENT_EXT: &str = "seg";
+const CURSOR_FILE: &str = "cursor";
+
+/// 持久化通道的编解码
+pub trait Codec<T>: Send + Sync + 'static {
+    fn encode(&self, item: &T) -> anyhow::Result<Vec<u8>>;
+    fn decode(&self, data: &[u8]) -> anyhow::Result<T>;
+}
+
+/// 落盘的持久化通道
+/// 数据追加写入目录下的segment文件，接收方ack后推进并持久化游标，游标之前的segment会被删除，
+/// 重启后从游标开始重新投递未ack的数据
+pub struct PersistChannel<T> {
+    inner: Arc<Inner<T>>,
+}
+
+impl<T> Clone for PersistChannel<T> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct PersistOptions {
+    segment_size: u64,
+    max_record_size: usize,
+    sync: bool,
+}
+
+impl Default for PersistOptions {
+    fn default() -> Self {
+        Self {
+            segment_size: 64 * 1024 * 1024,
+            max_record_size: 64 * 1024 * 1024,
+            sync: false,
+        }
+    }
+}
+
+impl PersistOptions {
+    // roll to a new segment when the current one is larger than size
+    pub fn segment_size(mut self, size: u64) -> Self {
+        self.segment_size = size.max(1);
+        self
+    }
+    // send fails on a larger encoded item, a larger length read from disk is treated as corruption
+    pub fn max_record_size(mut self, size: usize) -> Self {
+        self.max_record_size = size.min(u32::MAX as usize);
+        self
+    }
+    // fsync after every send
+    pub fn sync(mut self, sync: bool) -> Self {
+        self.sync = sync;
+        self
+    }
+    pub async fn open<T, P, C>(self, dir: P, codec: C) -> std::io::Result<PersistChannel<T>>
+    where
+        T: Send + 'static,
+        P: AsRef<Path>,
+        C: Codec<T>,
+    {
+        PersistChannel::open_inner(dir.as_ref(), Box::new(codec), self).await
+    }
+}
+
+// an item with the sequence used to ack it
+#[derive(Debug, Clone, PartialEq)]
+pub struct PersistItem<T> {
+    pub seq: u64,
+    pub value: T,
+}
+
+struct Inner<T> {
+    dir: PathBuf,
+    codec: Box<dyn Codec<T>>,
+    options: PersistOptions,
+    status: AtomicBool,
+    notify: Notify,
+    // the number of records written, readers never pass it
+    written: AtomicU64,
+    // the next sequence to deliver
+    delivered: AtomicU64,
+    writer: Mutex<Writer>,
+    reader: Mutex<Reader>,
+    acks: Mutex<Acks>,
+}
+
+struct Writer {
+    file: File,
+    // the length of the complete records in file
+    size: u64,
+    next: u64,
+    // a send was dropped during the write, the file may hold a torn record after size
+    dirty: bool,
+}
+
+struct Reader {
+    segment: Option<ReadSegment>,
+    // the sequence of the next record
+    next: u64,
+}
+
+struct ReadSegment {
+    base: u64,
+    file: File,
+    // the offset of the next record
+    offset: u64,
+    // false if a recv was dropped during the read, the file must seek back to offset
+    synced: bool,
+}
+
+struct Acks {
+    // every sequence before cursor is acked
+    cursor: u64,
+    pending: BTreeSet<u64>,
+}
+
+fn segment_path(dir: &Path, base: u64) -> PathBuf {
+    dir.join(format!("{:020}.{}", base, SEGMENT_EXT))
+}
+
+// the bases of all segments in ascending order
+async fn list_segments(dir: &Path) -> std::io::Result<Vec<u64>> {
+    let mut list = vec![];
+    let mut entries = tokio::fs::read_dir(dir).await?;
+    while let Some(entry) = entries.next_entry().await? {
+        let path = entry.path();
+        if path.extension().and_then(|s| s.to_str()) != Some(SEGMENT_EXT) {
+            continue;
+        }
+        if let Some(base) = path
+            .file_stem()
+            .and_then(|s| s.to_str())
+            .and_then(|s| s.parse::<u64>().ok())
+        {
+            list.push(base);
+        }
+    }
+    list.sort();
+    Ok(list)
+}
+
+// read one record, None at the end of the segment or on a torn tail
+async fn read_record(file: &mut File, max: usize) -> std::io::Result<Option<Vec<u8>>> {
+    let mut len = [0u8; 4];
+    match file.read_exact(&mut len).await {
+        Ok(_) => {}
+        Err(e) if e.kind() == ErrorKind::UnexpectedEof => return Ok(None),
+        Err(e) => return Err(e),
+    }
+    let len = u32::from_le_bytes(len) as usize;
+    if len > max {
+        return Err(std::io::Error::new(
+            ErrorKind::InvalidData,
+            format!(
+                "PersistChannel: record length[{}] is larger than {}",
+                len, max
+            ),
+        ));
+    }
+    let mut data = vec![0u8; len];
+    match file.read_exact(&mut data).await {
+        Ok(_) => Ok(Some(data)),
+        Err(e) if e.kind() == ErrorKind::UnexpectedEof => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+async fn read_cursor(dir: &Path) -> std::io::Result<u64> {
+    match tokio::fs::read(dir.join(CURSOR_FILE)).await {
+        Ok(data) if data.len() == 8 => Ok(u64::from_le_bytes(data.try_into().unwrap())),
+        Ok(_) => Err(std::io::Error::new(
+            ErrorKind::InvalidData,
+            "PersistChannel: broken cursor file",
+        )),
+        Err(e) if e.kind() == ErrorKind::NotFound => Ok(0),
+        Err(e) => Err(e),
+    }
+}
+
+// write to a temp file and rename, so the cursor is never half written
+async fn write_cursor(dir: &Path, cursor: u64) -> std::io::Result<()> {
+    let tmp = dir.join(format!("{}.tmp", CURSOR_FILE));
+    tokio::fs::write(&tmp, cursor.to_le_bytes()).await?;
+    tokio::fs::rename(&tmp, dir.join(CURSOR_FILE)).await
+}
+
+impl<T: Send + 'static> PersistChannel<T> {
+    pub fn options() -> PersistOptions {
+        PersistOptions::default()
+    }
+    pub async fn open<P: AsRef<Path>, C: Codec<T>>(dir: P, codec: C) -> std::io::Result<Self> {
+        PersistOptions::default().open(dir, codec).await
+    }
+    async fn open_inner(
+        dir: &Path,
+        codec: Box<dyn Codec<T>>,
+        options: PersistOptions,
+    ) -> std::io::Result<Self> {
+        tokio::fs::create_dir_all(dir).await?;
+        let cursor = read_cursor(dir).await?;
+        let segments = list_segments(dir).await?;
+        let base = segments.last().copied().unwrap_or(cursor);
+        let path = segment_path(dir, base);
+        let mut file = OpenOptions::new()
+            .create(true)
+            .read(true)
+            .append(true)
+            .open(&path)
+            .await?;
+        // count the records of the last segment and cut a torn tail
+        let mut next = base;
+        let mut size = 0u64;
+        file.seek(SeekFrom::Start(0)).await?;
+        while let Some(data) = read_record(&mut file, options.max_record_size).await? {
+            next += 1;
+            size += 4 + data.len() as u64;
+        }
+        if file.metadata().await?.len() > size {
+            file.set_len(size).await?;
+        }
+        let reader = Reader {
+            segment: None,
+            next: cursor.min(next),
+        };
+        let inner = Inner {
+            dir: dir.to_path_buf(),
+            codec,
+            options,
+            status: AtomicBool::new(true),
+            notify: Notify::new(),
+            written: AtomicU64::new(next),
+            delivered: AtomicU64::new(reader.next),
+            writer: Mutex::new(Writer {
+                file,
+                size,
+                next,
+                dirty: false,
+            }),
+            reader: Mutex::new(reader),
+            acks: Mutex::new(Acks {
+                cursor,
+                pending: BTreeSet::new(),
+            }),
+        };
+        Ok(Self {
+            inner: Arc::new(inner),
+        })
+    }
+    // the item is written to the OS when send returns and on disk only with sync(true),
+    // the sequence is returned
+    pub async fn send(&self, value: T) -> ChannelResult<u64, SendError<T>> {
+        if self.is_closed() {
+            return SendError::CLOSED(value).into_err();
+        }
+        let data = match self.inner.codec.encode(&value) {
+            Ok(o) => o,
+            Err(e) => return SendError::UNKNOWN(value, e.to_string()).into_err(),
+        };
+        match self.append(&data).await {
+            Ok(seq) => Ok(seq),
+            Err(e) => SendError::UNKNOWN(value, e.to_string()).into_err(),
+        }
+    }
+    async fn append(&self, data: &[u8]) -> std::io::Result<u64> {
+        if data.len() > self.inner.options.max_record_size {
+            return Err(std::io::Error::new(
+                ErrorKind::InvalidInput,
+                format!(
+                    "PersistChannel: record length[{}] is larger than {}",
+                    data.len(),
+                    self.inner.options.max_record_size
+                ),
+            ));
+        }
+        let mut writer = self.inner.writer.lock().await;
+        // cut what a dropped send left behind, the records are only counted after a full write
+        if writer.dirty {
+            let size = writer.size;
+            writer.file.set_len(size).await?;
+            writer.dirty = false;
+        }
+        if writer.size >= self.inner.options.segment_size {
+            let path = segment_path(&self.inner.dir, writer.next);
+            writer.file = OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(path)
+                .await?;
+            writer.size = 0;
+        }
+        let mut buf = Vec::with_capacity(4 + data.len());
+        buf.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        buf.extend_from_slice(data);
+        writer.dirty = true;
+        writer.file.write_all(&buf).await?;
+        writer.file.flush().await?;
+        if self.inner.options.sync {
+            writer.file.sync_data().await?;
+        }
+        let seq = writer.next;
+        writer.next += 1;
+        writer.size += buf.len() as u64;
+        writer.dirty = false;
+        self.inner.written.store(writer.next, Ordering::SeqCst);
+        self.inner.notify.notify_waiters();
+        Ok(seq)
+    }
+    // wait for the next item, return CLOSED after the closed channel is drained
+    pub async fn recv(&self) -> ChannelResult<PersistItem<T>, RecvError> {
+        loop {
+            let notified = self.inner.notify.notified();
+            let mut reader = self.inner.reader.lock().await;
+            if reader.next < self.inner.written.load(Ordering::SeqCst) {
+                // a record which can not be decoded is skipped, the caller may ack past it
+                return match self.read_next(&mut reader).await {
+                    Ok((seq, data)) => match self.inner.codec.decode(&data) {
+                        Ok(value) => Ok(PersistItem { seq, value }),
+                        Err(e) => RecvError::DECODE(seq, e.to_string()).into_err(),
+                    },
+                    Err(e) => RecvError::UNKNOWN(e.to_string()).into_err(),
+                };
+            }
+            drop(reader);
+            if self.is_closed() {
+                return RecvError::CLOSED.into_err();
+            }
+            notified.await;
+        }
+    }
+    // the reader only moves on after a whole record is read,
+    // a recv dropped halfway leaves the file unsynced and the next read seeks back
+    async fn read_next(&self, reader: &mut Reader) -> anyhow::Result<(u64, Vec<u8>)> {
+        let seq = reader.next;
+        let max = self.inner.options.max_record_size;
+        loop {
+            if let Some(segment) = reader.segment.as_mut() {
+                if !segment.synced {
+                    segment.file.seek(SeekFrom::Start(segment.offset)).await?;
+                }
+                segment.synced = false;
+                if let Some(data) = read_record(&mut segment.file, max).await? {
+                    segment.offset += 4 + data.len() as u64;
+                    segment.synced = true;
+                    reader.next += 1;
+                    self.inner.delivered.store(reader.next, Ordering::SeqCst);
+                    return Ok((seq, data));
+                }
+            }
+            // open the segment holding seq and skip the records before it
+            let segments = list_segments(&self.inner.dir).await?;
+            let base = match segments.iter().rev().find(|b| **b <= seq) {
+                Some(b) => *b,
+                None => anyhow::bail!("PersistChannel: segment of seq[{}] not found", seq),
+            };
+            if matches!(&reader.segment, Some(s) if s.base == base) {
+                anyhow::bail!("PersistChannel: record seq[{}] not found", seq);
+            }
+            let mut file = File::open(segment_path(&self.inner.dir, base)).await?;
+            let mut offset = 0;
+            for _ in base..seq {
+                match read_record(&mut file, max).await? {
+                    Some(data) => offset += 4 + data.len() as u64,
+                    None => anyhow::bail!("PersistChannel: record seq[{}] not found", seq),
+                }
+            }
+            reader.segment = Some(ReadSegment {
+                base,
+                file,
+                offset,
+                synced: true,
+            });
+        }
+    }
+    // every item before cursor is acked, the cursor is persisted and old segments are removed,
+    // only a delivered item can be acked
+    pub async fn ack(&self, seq: u64) -> std::io::Result<()> {
+        let delivered = self.inner.delivered.load(Ordering::SeqCst);
+        if seq >= delivered {
+            return Err(std::io::Error::new(
+                ErrorKind::InvalidInput,
+                format!("PersistChannel: seq[{}] is not delivered yet", seq),
+            ));
+        }
+        let mut acks = self.inner.acks.lock().await;
+        if seq < acks.cursor {
+            return Ok(());
+        }
+        acks.pending.insert(seq);
+        let mut cursor = acks.cursor;
+        while acks.pending.remove(&cursor) {
+            cursor += 1;
+        }
+        if cursor == acks.cursor {
+            return Ok(());
+        }
+        write_cursor(&self.inner.dir, cursor).await?;
+        acks.cursor = cursor;
+        self.gc(cursor).await
+    }
+    pub async fn cursor(&self) -> u64 {
+        self.inner.acks.lock().await.cursor
+    }
+    // remove the segments whose records are all acked and delivered
+    async fn gc(&self, cursor: u64) -> std::io::Result<()> {
+        let limit = cursor.min(self.inner.delivered.load(Ordering::SeqCst));
+        let segments = list_segments(&self.inner.dir).await?;
+        for w in segments.windows(2) {
+            if w[1] > limit {
+                break;
+            }
+            match tokio::fs::remove_file(segment_path(&self.inner.dir, w[0])).await {
+                Err(e) if e.kind() != ErrorKind::NotFound => return Err(e),
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+    // the number of items sent but not delivered
+    pub fn len(&self) -> usize {
+        let written = self.inner.written.load(Ordering::SeqCst);
+        written.saturating_sub(self.inner.delivered.load(Ordering::SeqCst)) as usize
+    }
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+    pub fn close(&self) {
+        self.inner.status.store(false, Ordering::SeqCst);
+        self.inner.notify.notify_waiters();
+    }
+    pub fn is_closed(&self) -> bool {
+        !self.inner.status.load(Ordering::SeqCst)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Codec, PersistChannel};
+    use crate::channel::RecvError;
+    use futures::FutureExt;
+    use std::future::Future;
+    use std::io::ErrorKind;
+    use std::time::Duration;
+
+    struct StringCodec;
+
+    impl Codec<String> for StringCodec {
+        fn encode(&self, item: &String) -> anyhow::Result<Vec<u8>> {
+            Ok(item.as_bytes().to_vec())
+        }
+        fn decode(&self, data: &[u8]) -> anyhow::Result<String> {
+            if data == b"bad" {
+                anyhow::bail!("bad record");
+            }
+            Ok(String::from_utf8(data.to_vec())?)
+        }
+    }
+
+    async fn segment_count(dir: &std::path::Path) -> usize {
+        super::list_segments(dir).await.unwrap().len()
+    }
+
+    // poll the future n times with a pause for the file io in between, then drop it
+    async fn poll_then_drop<F: Future>(fut: F, n: usize) -> Option<F::Output> {
+        let mut fut = Box::pin(fut);
+        for _ in 0..n {
+            if let Some(o) = (&mut fut).now_or_never() {
+                return Some(o);
+            }
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+        None
+    }
+
+    #[tokio::test]
+    async fn test_persist_channel() {
+        let dir = std::env::temp_dir().join(format!("wd_tools_persist_{}", std::process::id()));
+        let _ = tokio::fs::remove_dir_all(&dir).await;
+        let options = PersistChannel::<String>::options().segment_size(32);
+
+        let chan = options.clone().open(&dir, StringCodec).await.unwrap();
+        let receiver = chan.clone();
+        let handle = tokio::spawn(async move { receiver.recv().await });
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        for i in 0..10 {
+            assert_eq!(chan.send(format!("item-{}", i)).await.unwrap(), i);
+        }
+        assert_eq!(handle.await.unwrap().unwrap().value, "item-0");
+        for i in 1..10 {
+            let item = chan.recv().await.unwrap();
+            assert_eq!((item.seq, item.value), (i, format!("item-{}", i)));
+        }
+        for seq in [1, 0, 2, 4] {
+            chan.ack(seq).await.unwrap();
+        }
+        assert_eq!(chan.cursor().await, 3);
+        assert!(segment_count(&dir).await < 10);
+        drop(chan);
+
+        // the items not acked are delivered again after restart
+        let chan = options.open(&dir, StringCodec).await.unwrap();
+        let item = chan.recv().await.unwrap();
+        assert_eq!((item.seq, item.value.as_str()), (3, "item-3"));
+        assert_eq!(chan.len(), 6);
+        chan.send("item-10".into()).await.unwrap();
+        for seq in 3..=10 {
+            if seq > 3 {
+                assert_eq!(chan.recv().await.unwrap().seq, seq);
+            }
+            chan.ack(seq).await.unwrap();
+        }
+        assert_eq!(segment_count(&dir).await, 1);
+        chan.close();
+        assert_eq!(chan.recv().await, Err(RecvError::CLOSED));
+        let _ = tokio::fs::remove_dir_all(&dir).await;
+    }
+
+    #[tokio::test]
+    async fn test_persist_cancel() {
+        let dir =
+            std::env::temp_dir().join(format!("wd_tools_persist_cancel_{}", std::process::id()));
+        let _ = tokio::fs::remove_dir_all(&dir).await;
+        let chan = PersistChannel::open(&dir, StringCodec).await.unwrap();
+
+        // a send dropped during the write leaves no record behind
+        let mut sent = vec![];
+        for n in 1..=4 {
+            let value = format!("cancel-{}", n);
+            if let Some(seq) = poll_then_drop(chan.send(value.clone()), n).await {
+                sent.push((seq.unwrap(), value));
+            }
+        }
+        for i in 0..3 {
+            let value = format!("item-{}", i);
+            let seq = chan.send(value.clone()).await.unwrap();
+            sent.push((seq, value));
+        }
+        assert_eq!(chan.len(), sent.len());
+
+        // a recv dropped during the read does not lose or shift a record
+        let mut got = vec![];
+        for n in 1..=4 {
+            if let Some(item) = poll_then_drop(chan.recv(), n).await {
+                let item = item.unwrap();
+                got.push((item.seq, item.value));
+            }
+        }
+        while got.len() < sent.len() {
+            let item = chan.recv().await.unwrap();
+            got.push((item.seq, item.value));
+        }
+        assert_eq!(got, sent);
+        for (i, (seq, _)) in sent.iter().enumerate() {
+            assert_eq!(*seq, i as u64);
+        }
+        let _ = tokio::fs::remove_dir_all(&dir).await;
+    }
+
+    #[tokio::test]
+    async fn test_persist_record_size() {
+        let dir =
+            std::env::temp_dir().join(format!("wd_tools_persist_size_{}", std::process::id()));
+        let _ = tokio::fs::remove_dir_all(&dir).await;
+        let options = PersistChannel::<String>::options().max_record_size(8);
+        let chan = options.clone().open(&dir, StringCodec).await.unwrap();
+        assert!(chan.send("0123456789".into()).await.is_err());
+        assert_eq!(chan.send("01234567".into()).await.unwrap(), 0);
+        drop(chan);
+
+        // a broken length on disk is an error instead of a huge allocation
+        let path = super::segment_path(&dir, 0);
+        let mut data = tokio::fs::read(&path).await.unwrap();
+        data.extend_from_slice(&u32::MAX.to_le_bytes());
+        data.extend_from_slice(b"xx");
+        tokio::fs::write(&path, data).await.unwrap();
+        let err = options.open(&dir, StringCodec).await.err().unwrap();
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+        let _ = tokio::fs::remove_dir_all(&dir).await;
+    }
+
+    #[tokio::test]
+    async fn test_persist_decode_error() {
+        let dir =
+            std::env::temp_dir().join(format!("wd_tools_persist_decode_{}", std::process::id()));
+        let _ = tokio::fs::remove_dir_all(&dir).await;
+        let chan = PersistChannel::open(&dir, StringCodec).await.unwrap();
+        for value in ["bad", "item-1", "item-2"] {
+            chan.send(value.to_string()).await.unwrap();
+        }
+        // an item not delivered can not be acked
+        let err = chan.ack(0).await.unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidInput);
+
+        // the broken record is reported once with its seq, the later ones are still delivered
+        match chan.recv().await {
+            Err(RecvError::DECODE(seq, _)) => chan.ack(seq).await.unwrap(),
+            res => panic!("unexpected result: {:?}", res.map(|i| i.seq)),
+        }
+        for seq in 1..3 {
+            let item = chan.recv().await.unwrap();
+            assert_eq!((item.seq, item.value), (seq, format!("item-{}", seq)));
+            chan.ack(seq).await.unwrap();
+        }
+        assert_eq!(chan.cursor().await, 3);
+        assert!(chan.ack(3).await.is_err());
+        let _ = tokio::fs::remove_dir_all(&dir).await;
+    }
+}
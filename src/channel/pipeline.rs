@@ -0,0 +1,213 @@
+use crate::channel::{Channel, Receiver, Sender};
+use futures::StreamExt;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use tokio::task::JoinHandle;
+
+/// 流水线
+/// 每个stage有自己的有界通道和并发数，上游关闭后逐级关闭，任意stage出错会关闭整条流水线
+pub struct Pipeline<T> {
+    receiver: Receiver<T>,
+    state: Arc<PipelineState>,
+}
+
+type Closer = Box<dyn Fn() + Send + Sync>;
+
+#[derive(Default)]
+struct PipelineState {
+    error: Mutex<Option<anyhow::Error>>,
+    closers: Mutex<Vec<Closer>>,
+    tasks: Mutex<Vec<JoinHandle<()>>>,
+}
+
+impl PipelineState {
+    fn add_channel<T: Send + 'static>(&self, sender: &Sender<T>) {
+        let chan = sender.channel().clone();
+        self.closers
+            .lock()
+            .unwrap()
+            .push(Box::new(move || chan.close_recv()));
+    }
+    fn spawn(&self, fut: impl Future<Output = ()> + Send + 'static) {
+        self.tasks.lock().unwrap().push(tokio::spawn(fut));
+    }
+    // keep the first error and shut down every stage
+    fn fail(&self, err: anyhow::Error) {
+        {
+            let mut error = self.error.lock().unwrap();
+            if error.is_none() {
+                *error = Some(err);
+            }
+        }
+        self.shutdown();
+    }
+    fn shutdown(&self) {
+        for close in self.closers.lock().unwrap().iter() {
+            close();
+        }
+    }
+}
+
+impl<T: Send + 'static> Pipeline<T> {
+    pub fn new(receiver: Receiver<T>) -> Self {
+        Self {
+            receiver,
+            state: Arc::new(PipelineState::default()),
+        }
+    }
+    // feed the pipeline from an iterator
+    pub fn source<I>(cap: usize, iter: I) -> Self
+    where
+        I: IntoIterator<Item = T> + Send + 'static,
+        I::IntoIter: Send,
+    {
+        let (sender, receiver) = Channel::new(cap);
+        let pipeline = Self::new(receiver);
+        pipeline.state.add_channel(&sender);
+        pipeline.state.spawn(async move {
+            for item in iter {
+                if sender.send(item).await.is_err() {
+                    return;
+                }
+            }
+        });
+        pipeline
+    }
+    fn next_stage<O: Send + 'static>(&self, cap: usize) -> (Sender<O>, Pipeline<O>) {
+        let (sender, receiver) = Channel::new(cap);
+        self.state.add_channel(&sender);
+        let next = Pipeline {
+            receiver,
+            state: self.state.clone(),
+        };
+        (sender, next)
+    }
+    // run f on concurrency workers, the output order is not kept
+    pub fn stage<O, F, Fut>(self, concurrency: usize, cap: usize, f: F) -> Pipeline<O>
+    where
+        O: Send + 'static,
+        F: Fn(T) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = anyhow::Result<O>> + Send + 'static,
+    {
+        let (sender, next) = self.next_stage(cap);
+        let f = Arc::new(f);
+        for _ in 0..concurrency.max(1) {
+            let input = self.receiver.clone();
+            let sender = sender.clone();
+            let f = f.clone();
+            let state = self.state.clone();
+            self.state.spawn(async move {
+                while let Ok(item) = input.recv().await {
+                    match f(item).await {
+                        Ok(o) => {
+                            if sender.send(o).await.is_err() {
+                                return;
+                            }
+                        }
+                        Err(e) => return state.fail(e),
+                    }
+                }
+            });
+        }
+        next
+    }
+    // run up to concurrency f at the same time, the output keeps the input order
+    pub fn stage_ordered<O, F, Fut>(self, concurrency: usize, cap: usize, f: F) -> Pipeline<O>
+    where
+        O: Send + 'static,
+        F: Fn(T) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = anyhow::Result<O>> + Send + 'static,
+    {
+        let (sender, next) = self.next_stage(cap);
+        let state = self.state.clone();
+        let input = self.receiver;
+        self.state.spawn(async move {
+            let mut output = input.map(f).buffered(concurrency.max(1));
+            while let Some(res) = output.next().await {
+                match res {
+                    Ok(o) => {
+                        if sender.send(o).await.is_err() {
+                            return;
+                        }
+                    }
+                    Err(e) => return state.fail(e),
+                }
+            }
+        });
+        next
+    }
+    // close every stage, the items in the channels are dropped
+    pub fn shutdown(&self) {
+        self.state.shutdown()
+    }
+    // wait for all stages and drop the output
+    pub async fn wait(self) -> anyhow::Result<()> {
+        self.finish(|_| {}).await
+    }
+    pub async fn collect(self) -> anyhow::Result<Vec<T>> {
+        let mut list = vec![];
+        self.finish(|t| list.push(t)).await?;
+        Ok(list)
+    }
+    async fn finish(self, mut f: impl FnMut(T)) -> anyhow::Result<()> {
+        while let Ok(t) = self.receiver.recv().await {
+            f(t);
+        }
+        let tasks = std::mem::take(&mut *self.state.tasks.lock().unwrap());
+        for task in tasks {
+            if let Err(e) = task.await {
+                self.state
+                    .fail(anyhow::anyhow!("Pipeline: stage panic: {}", e));
+            }
+        }
+        match self.state.error.lock().unwrap().take() {
+            Some(e) => Err(e),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Pipeline;
+    use std::time::Duration;
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_pipeline() {
+        let list = Pipeline::source(8, 0..100usize)
+            .stage(4, 8, |i| async move { Ok(i * 2) })
+            .stage_ordered(4, 8, |i| async move {
+                tokio::time::sleep(Duration::from_millis((i % 3) as u64)).await;
+                Ok(i)
+            })
+            .collect()
+            .await
+            .unwrap();
+        assert_eq!(list.len(), 100);
+
+        let list = Pipeline::source(8, 0..100usize)
+            .stage_ordered(8, 8, |i| async move {
+                tokio::time::sleep(Duration::from_millis((100 - i as u64) % 5)).await;
+                Ok(i + 1)
+            })
+            .collect()
+            .await
+            .unwrap();
+        assert_eq!(list, (1..=100).collect::<Vec<_>>());
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_pipeline_error() {
+        let res = Pipeline::source(4, 0..usize::MAX)
+            .stage(4, 4, |i| async move {
+                if i == 50 {
+                    return Err(anyhow::anyhow!("bad item {}", i));
+                }
+                Ok(i)
+            })
+            .stage(2, 4, |i| async move { Ok(i) })
+            .wait()
+            .await;
+        assert_eq!(res.unwrap_err().to_string(), "bad item 50");
+    }
+}
@@ -0,0 +1,398 @@
+use crate::channel::{ChannelError, ChannelResult, RecvError, SendError};
+use std::cmp::Ordering as CmpOrdering;
+use std::collections::{BinaryHeap, VecDeque};
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Waker};
+
+/// 优先级通道
+/// 接收方总是先拿到优先级最高的数据，优先级相同时先进先出，容量/关闭/错误语义与Channel一致
+#[derive(Debug)]
+pub struct PriorityChannel<T> {
+    status: AtomicBool,
+    cap: usize,
+    queue: Mutex<PriorityQueue<T>>,
+}
+
+#[derive(Debug)]
+struct PriorityQueue<T> {
+    heap: BinaryHeap<Item<T>>,
+    seq: u64,
+    sender_waker: VecDeque<Waker>,
+    receiver_waker: VecDeque<Waker>,
+}
+
+#[derive(Debug)]
+struct Item<T> {
+    priority: u64,
+    seq: u64,
+    value: T,
+}
+
+impl<T> PartialEq for Item<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.priority == other.priority && self.seq == other.seq
+    }
+}
+impl<T> Eq for Item<T> {}
+impl<T> PartialOrd for Item<T> {
+    fn partial_cmp(&self, other: &Self) -> Option<CmpOrdering> {
+        Some(self.cmp(other))
+    }
+}
+// higher priority first, the smaller seq first on ties
+impl<T> Ord for Item<T> {
+    fn cmp(&self, other: &Self) -> CmpOrdering {
+        self.priority
+            .cmp(&other.priority)
+            .then_with(|| other.seq.cmp(&self.seq))
+    }
+}
+
+fn register(list: &mut VecDeque<Waker>, waker: &Waker) {
+    if !list.iter().any(|w| w.will_wake(waker)) {
+        list.push_back(waker.clone());
+    }
+}
+
+#[derive(Debug)]
+pub struct PrioritySender<T> {
+    chan: Arc<PriorityChannel<T>>,
+}
+
+#[derive(Debug)]
+pub struct PriorityReceiver<T> {
+    chan: Arc<PriorityChannel<T>>,
+}
+
+impl<T> Clone for PrioritySender<T> {
+    fn clone(&self) -> Self {
+        Self {
+            chan: self.chan.clone(),
+        }
+    }
+}
+
+impl<T> Clone for PriorityReceiver<T> {
+    fn clone(&self) -> Self {
+        Self {
+            chan: self.chan.clone(),
+        }
+    }
+}
+
+impl<T> PriorityChannel<T> {
+    #[allow(clippy::new_ret_no_self)]
+    pub fn new(cap: usize) -> (PrioritySender<T>, PriorityReceiver<T>) {
+        let chan = Arc::new(PriorityChannel {
+            status: AtomicBool::new(true),
+            cap: cap.max(1),
+            queue: Mutex::new(PriorityQueue {
+                heap: BinaryHeap::new(),
+                seq: 0,
+                sender_waker: VecDeque::new(),
+                receiver_waker: VecDeque::new(),
+            }),
+        });
+        (
+            PrioritySender { chan: chan.clone() },
+            PriorityReceiver { chan },
+        )
+    }
+    fn _try_send(
+        &self,
+        value: T,
+        priority: u64,
+        waker: Option<&Waker>,
+    ) -> ChannelResult<(), SendError<T>> {
+        if self.is_closed() {
+            return SendError::CLOSED(value).into_err();
+        }
+        let mut queue = match self.queue.lock() {
+            Ok(o) => o,
+            Err(e) => return SendError::UNKNOWN(value, e.to_string()).into_err(),
+        };
+        if queue.heap.len() >= self.cap {
+            if let Some(w) = waker {
+                register(&mut queue.sender_waker, w);
+            }
+            return SendError::FULL(value).into_err();
+        }
+        let seq = queue.seq;
+        queue.seq += 1;
+        queue.heap.push(Item {
+            priority,
+            seq,
+            value,
+        });
+        if let Some(w) = queue.receiver_waker.pop_front() {
+            w.wake();
+        }
+        Ok(())
+    }
+    fn _try_recv(&self, waker: Option<&Waker>) -> ChannelResult<T, RecvError> {
+        let mut queue = match self.queue.lock() {
+            Ok(o) => o,
+            Err(e) => return RecvError::UNKNOWN(e.to_string()).into_err(),
+        };
+        if let Some(item) = queue.heap.pop() {
+            if let Some(w) = queue.sender_waker.pop_front() {
+                w.wake();
+            }
+            return Ok(item.value);
+        }
+        if self.is_closed() {
+            return RecvError::CLOSED.into_err();
+        }
+        if let Some(w) = waker {
+            register(&mut queue.receiver_waker, w);
+        }
+        RecvError::EMPTY.into_err()
+    }
+    // remove the waker of a finished future
+    fn remove_waker(&self, waker: &Waker, sender: bool) {
+        let mut queue = match self.queue.lock() {
+            Ok(o) => o,
+            Err(_) => return,
+        };
+        let list = if sender {
+            &mut queue.sender_waker
+        } else {
+            &mut queue.receiver_waker
+        };
+        list.retain(|w| !w.will_wake(waker));
+    }
+    // remove the waker of a cancelled future, pass a consumed notification on
+    fn cancel_waker(&self, waker: &Waker, sender: bool) {
+        let mut queue = match self.queue.lock() {
+            Ok(o) => o,
+            Err(_) => return,
+        };
+        let ready = if sender {
+            queue.heap.len() < self.cap
+        } else {
+            !queue.heap.is_empty()
+        };
+        let list = if sender {
+            &mut queue.sender_waker
+        } else {
+            &mut queue.receiver_waker
+        };
+        let len = list.len();
+        list.retain(|w| !w.will_wake(waker));
+        if len == list.len() && ready {
+            if let Some(w) = list.pop_front() {
+                w.wake();
+            }
+        }
+    }
+    fn len(&self) -> usize {
+        self.queue.lock().unwrap().heap.len()
+    }
+    fn close(&self) {
+        self.status.store(false, Ordering::Relaxed);
+        let mut queue = self.queue.lock().unwrap();
+        for i in queue.receiver_waker.drain(..) {
+            i.wake();
+        }
+        for i in queue.sender_waker.drain(..) {
+            i.wake();
+        }
+    }
+    fn is_closed(&self) -> bool {
+        !self.status.load(Ordering::Relaxed)
+    }
+}
+
+impl<T> PrioritySender<T> {
+    // the bigger priority is received first
+    pub fn try_send(&self, value: T, priority: u64) -> ChannelResult<(), SendError<T>> {
+        self.chan._try_send(value, priority, None)
+    }
+    pub fn send(&self, value: T, priority: u64) -> PrioritySendFuture<T> {
+        PrioritySendFuture {
+            chan: self.chan.clone(),
+            value: Some(value),
+            priority,
+            waker: None,
+        }
+    }
+    pub fn len(&self) -> usize {
+        self.chan.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+    pub fn close(&self) {
+        self.chan.close()
+    }
+    pub fn is_closed(&self) -> bool {
+        self.chan.is_closed()
+    }
+}
+
+impl<T> PriorityReceiver<T> {
+    pub fn try_recv(&self) -> ChannelResult<T, RecvError> {
+        self.chan._try_recv(None)
+    }
+    pub fn recv(&self) -> PriorityRecvFuture<T> {
+        PriorityRecvFuture {
+            chan: self.chan.clone(),
+            waker: None,
+        }
+    }
+    pub fn len(&self) -> usize {
+        self.chan.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+    pub fn close(&self) {
+        self.chan.close()
+    }
+    pub fn is_closed(&self) -> bool {
+        self.chan.is_closed()
+    }
+}
+
+pub struct PrioritySendFuture<T> {
+    chan: Arc<PriorityChannel<T>>,
+    value: Option<T>,
+    priority: u64,
+    waker: Option<Waker>,
+}
+
+// the value is never pinned
+impl<T> Unpin for PrioritySendFuture<T> {}
+
+impl<T> Future for PrioritySendFuture<T> {
+    type Output = ChannelResult<(), SendError<T>>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let value = match self.value.take() {
+            Some(s) => s,
+            None => panic!("PrioritySendFuture polled after completion"),
+        };
+        match self.chan._try_send(value, self.priority, Some(cx.waker())) {
+            Err(SendError::FULL(value)) => {
+                self.value = Some(value);
+                self.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+            res => {
+                if let Some(w) = self.waker.take() {
+                    self.chan.remove_waker(&w, true);
+                }
+                Poll::Ready(res)
+            }
+        }
+    }
+}
+
+impl<T> Drop for PrioritySendFuture<T> {
+    fn drop(&mut self) {
+        if let Some(w) = self.waker.take() {
+            self.chan.cancel_waker(&w, true);
+        }
+    }
+}
+
+pub struct PriorityRecvFuture<T> {
+    chan: Arc<PriorityChannel<T>>,
+    waker: Option<Waker>,
+}
+
+impl<T> Future for PriorityRecvFuture<T> {
+    type Output = ChannelResult<T, RecvError>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        match self.chan._try_recv(Some(cx.waker())) {
+            Err(RecvError::EMPTY) => {
+                self.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+            res => {
+                if let Some(w) = self.waker.take() {
+                    self.chan.remove_waker(&w, false);
+                }
+                Poll::Ready(res)
+            }
+        }
+    }
+}
+
+impl<T> Drop for PriorityRecvFuture<T> {
+    fn drop(&mut self) {
+        if let Some(w) = self.waker.take() {
+            self.chan.cancel_waker(&w, false);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::channel::{PriorityChannel, RecvError, SendError};
+    use std::future::Future;
+    use std::pin::Pin;
+    use std::sync::Arc;
+    use std::task::{Context, Poll, Wake, Waker};
+    use std::time::Duration;
+
+    struct NoopWake;
+
+    impl Wake for NoopWake {
+        fn wake(self: Arc<Self>) {}
+    }
+
+    fn poll_once<F: Future + Unpin>(fut: &mut F) -> Poll<F::Output> {
+        let waker = Waker::from(Arc::new(NoopWake));
+        Pin::new(fut).poll(&mut Context::from_waker(&waker))
+    }
+
+    #[tokio::test]
+    async fn test_priority_channel() {
+        let (sender, receiver) = PriorityChannel::<&str>::new(4);
+        sender.try_send("low-1", 1).unwrap();
+        sender.try_send("high", 9).unwrap();
+        sender.try_send("low-2", 1).unwrap();
+        sender.try_send("mid", 5).unwrap();
+        assert_eq!(sender.try_send("full", 5), Err(SendError::FULL("full")));
+
+        let waiting = sender.clone();
+        let handle = tokio::spawn(async move { waiting.send("urgent", 10).await });
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        assert_eq!(receiver.recv().await, Ok("high"));
+        handle.await.unwrap().unwrap();
+
+        let mut list = vec![];
+        while let Ok(s) = receiver.try_recv() {
+            list.push(s);
+        }
+        assert_eq!(list, vec!["urgent", "mid", "low-1", "low-2"]);
+
+        sender.close();
+        assert_eq!(receiver.recv().await, Err(RecvError::CLOSED));
+        assert_eq!(sender.send("x", 1).await, Err(SendError::CLOSED("x")));
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_priority_two_receivers() {
+        let (sender, receiver) = PriorityChannel::<usize>::new(4);
+        let other = receiver.clone();
+        let mut first = Box::pin(receiver.recv());
+        let mut second = Box::pin(other.recv());
+        assert!(poll_once(&mut first).is_pending());
+        assert!(poll_once(&mut second).is_pending());
+        // the first is woken, but the second takes the item
+        sender.try_send(0, 1).unwrap();
+        assert_eq!(poll_once(&mut second), Poll::Ready(Ok(0)));
+        // the finished second receiver does not take the next notification
+        let waiting = tokio::spawn(first);
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        sender.try_send(1, 1).unwrap();
+        let res = tokio::time::timeout(Duration::from_secs(1), waiting).await;
+        assert_eq!(res.unwrap().unwrap(), Ok(1));
+    }
+}
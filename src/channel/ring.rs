@@ -0,0 +1,404 @@
+use std::cell::UnsafeCell;
+use std::collections::VecDeque;
+use std::mem::MaybeUninit;
+use std::ops::Deref;
+use std::sync::atomic::{AtomicPtr, AtomicU64, AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::task::Waker;
+
+// the slots allocated up front, larger channels grow on demand
+const INIT_SLOTS: usize = 1024;
+// set in enqueue_pos when the queue is full and a larger one is linked after it,
+// the positions are u64 so they never reach it, also on 32-bit targets
+const SEALED: u64 = 1 << 63;
+
+// keep the positions of senders and receivers on different cache lines
+#[repr(align(64))]
+struct CachePadded<T>(T);
+
+impl<T> Deref for CachePadded<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+struct Slot<T> {
+    // equals pos when the slot can be written, pos + 1 when it can be read
+    seq: AtomicU64,
+    value: UnsafeCell<MaybeUninit<T>>,
+}
+
+// the result of pushing into one queue
+enum Push<T> {
+    Ok,
+    // the logical capacity is reached
+    Full(T),
+    // the capacity allows more, but every slot is in use
+    Grow(T),
+    // a larger queue has taken over
+    Sealed(T),
+}
+
+// Vyukov bounded MPMC queue, the number of slots is a power of two,
+// the positions go on from the queue it takes over from
+struct RingQueue<T> {
+    buffer: Box<[Slot<T>]>,
+    mask: usize,
+    enqueue_pos: CachePadded<AtomicU64>,
+    dequeue_pos: CachePadded<AtomicU64>,
+    next: AtomicPtr<RingQueue<T>>,
+}
+
+impl<T> RingQueue<T> {
+    fn new(slots: usize, base: u64) -> Self {
+        let slots = slots.max(2).next_power_of_two();
+        let mask = slots - 1;
+        let buffer = (0..slots)
+            .map(|i| Slot {
+                // the first position from base that maps to the slot
+                seq: AtomicU64::new(base + ((i as u64).wrapping_sub(base) & mask as u64)),
+                value: UnsafeCell::new(MaybeUninit::uninit()),
+            })
+            .collect();
+        Self {
+            buffer,
+            mask,
+            enqueue_pos: CachePadded(AtomicU64::new(base)),
+            dequeue_pos: CachePadded(AtomicU64::new(base)),
+            next: AtomicPtr::new(std::ptr::null_mut()),
+        }
+    }
+    fn slots(&self) -> u64 {
+        self.mask as u64 + 1
+    }
+    fn slot(&self, pos: u64) -> &Slot<T> {
+        &self.buffer[pos as usize & self.mask]
+    }
+    // cap is the logical capacity counted from head, the position of the first item in the buffer
+    fn push(&self, value: T, cap: u64, head: impl Fn() -> u64) -> Push<T> {
+        let mut pos = self.enqueue_pos.load(Ordering::SeqCst);
+        loop {
+            if pos & SEALED != 0 {
+                return Push::Sealed(value);
+            }
+            let slot = self.slot(pos);
+            let seq = slot.seq.load(Ordering::Acquire);
+            let diff = seq as i64 - pos as i64;
+            if diff == 0 {
+                if pos.saturating_sub(head()) >= cap {
+                    return Push::Full(value);
+                }
+                match self.enqueue_pos.compare_exchange_weak(
+                    pos,
+                    pos + 1,
+                    Ordering::SeqCst,
+                    Ordering::SeqCst,
+                ) {
+                    Ok(_) => {
+                        unsafe { (*slot.value.get()).write(value) };
+                        slot.seq.store(pos + 1, Ordering::Release);
+                        return Push::Ok;
+                    }
+                    Err(p) => pos = p,
+                }
+            } else if diff < 0 {
+                if pos.saturating_sub(head()) >= cap {
+                    return Push::Full(value);
+                }
+                if pos.saturating_sub(self.dequeue_pos.load(Ordering::SeqCst)) >= self.slots() {
+                    return Push::Grow(value);
+                }
+                // a receiver has taken the slot but not released it yet
+                std::hint::spin_loop();
+                pos = self.enqueue_pos.load(Ordering::SeqCst);
+            } else {
+                pos = self.enqueue_pos.load(Ordering::SeqCst);
+            }
+        }
+    }
+    fn pop(&self) -> Option<T> {
+        let mut pos = self.dequeue_pos.load(Ordering::Relaxed);
+        loop {
+            let slot = self.slot(pos);
+            let seq = slot.seq.load(Ordering::Acquire);
+            let diff = seq as i64 - (pos + 1) as i64;
+            if diff == 0 {
+                match self.dequeue_pos.compare_exchange_weak(
+                    pos,
+                    pos + 1,
+                    Ordering::SeqCst,
+                    Ordering::Relaxed,
+                ) {
+                    Ok(_) => {
+                        let value = unsafe { (*slot.value.get()).assume_init_read() };
+                        slot.seq.store(pos + self.slots(), Ordering::Release);
+                        return Some(value);
+                    }
+                    Err(p) => pos = p,
+                }
+            } else if diff < 0 {
+                // empty, or a sender has taken the slot but not written it yet
+                return None;
+            } else {
+                pos = self.dequeue_pos.load(Ordering::Relaxed);
+            }
+        }
+    }
+    // sealed and every item before the seal is taken
+    fn is_drained(&self) -> bool {
+        let tail = self.enqueue_pos.load(Ordering::SeqCst);
+        tail & SEALED != 0 && self.dequeue_pos.load(Ordering::SeqCst) >= tail & !SEALED
+    }
+}
+
+impl<T> Drop for RingQueue<T> {
+    fn drop(&mut self) {
+        while self.pop().is_some() {}
+    }
+}
+
+/// 无锁队列，Channel的存储
+/// 槽位用完且容量允许时封存当前环形队列，在后面接一个两倍大小的新队列，
+/// 发送方转去写新队列，接收方取完旧队列再取新队列，扩容时也不用停下其他操作
+pub(crate) struct RingBuffer<T> {
+    // receivers take from head and senders put into tail, they differ only while growing
+    head: AtomicPtr<RingQueue<T>>,
+    tail: AtomicPtr<RingQueue<T>>,
+    // a receiver may still look at a drained queue, so every queue is kept until drop,
+    // each one doubles the last, together they take less than twice the largest one
+    queues: Mutex<Vec<*mut RingQueue<T>>>,
+}
+
+unsafe impl<T: Send> Send for RingBuffer<T> {}
+unsafe impl<T: Send> Sync for RingBuffer<T> {}
+
+impl<T> std::fmt::Debug for RingBuffer<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RingBuffer")
+            .field("len", &self.len())
+            .finish()
+    }
+}
+
+impl<T> RingBuffer<T> {
+    pub(crate) fn new(cap: usize) -> Self {
+        let ptr = Box::into_raw(Box::new(RingQueue::new(cap.min(INIT_SLOTS), 0)));
+        Self {
+            head: AtomicPtr::new(ptr),
+            tail: AtomicPtr::new(ptr),
+            queues: Mutex::new(vec![ptr]),
+        }
+    }
+    fn head(&self) -> &RingQueue<T> {
+        unsafe { &*self.head.load(Ordering::SeqCst) }
+    }
+    fn tail(&self) -> &RingQueue<T> {
+        unsafe { &*self.tail.load(Ordering::SeqCst) }
+    }
+    // the position of the first item
+    fn head_pos(&self) -> u64 {
+        self.head().dequeue_pos.load(Ordering::SeqCst)
+    }
+    // seal the full tail queue and link a larger one after it
+    fn grow(&self, full: &RingQueue<T>) {
+        let mut queues = self.queues.lock().unwrap();
+        if !std::ptr::eq(self.tail(), full) {
+            return;
+        }
+        let end = full.enqueue_pos.fetch_or(SEALED, Ordering::SeqCst);
+        let ptr = Box::into_raw(Box::new(RingQueue::new((full.mask + 1) * 2, end)));
+        queues.push(ptr);
+        full.next.store(ptr, Ordering::SeqCst);
+        self.tail.store(ptr, Ordering::SeqCst);
+    }
+    // the value is given back if cap is reached
+    pub(crate) fn push(&self, value: T, cap: usize) -> Result<(), T> {
+        let mut value = value;
+        loop {
+            let tail = self.tail();
+            match tail.push(value, cap as u64, || self.head_pos()) {
+                Push::Ok => return Ok(()),
+                Push::Full(v) => return Err(v),
+                Push::Grow(v) | Push::Sealed(v) => {
+                    value = v;
+                    self.grow(tail);
+                }
+            }
+        }
+    }
+    pub(crate) fn pop(&self) -> Option<T> {
+        loop {
+            let head = self.head();
+            if let Some(t) = head.pop() {
+                return Some(t);
+            }
+            if !head.is_drained() {
+                return None;
+            }
+            let next = head.next.load(Ordering::SeqCst);
+            if next.is_null() {
+                return None;
+            }
+            let _ = self.head.compare_exchange(
+                head as *const RingQueue<T> as *mut RingQueue<T>,
+                next,
+                Ordering::SeqCst,
+                Ordering::SeqCst,
+            );
+        }
+    }
+    // count the items still being written, only a snapshot under concurrent access
+    pub(crate) fn len(&self) -> usize {
+        let head = self.head_pos();
+        let tail = self.tail().enqueue_pos.load(Ordering::SeqCst) & !SEALED;
+        tail.saturating_sub(head) as usize
+    }
+    pub(crate) fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl<T> Drop for RingBuffer<T> {
+    fn drop(&mut self) {
+        for ptr in self.queues.get_mut().unwrap().drain(..) {
+            drop(unsafe { Box::from_raw(ptr) });
+        }
+    }
+}
+
+// parked wakers, each task is registered only once
+#[derive(Debug, Default)]
+pub(crate) struct Waiters {
+    list: Mutex<VecDeque<Waker>>,
+    // mirror of list.len(), so the fast path does not lock
+    count: AtomicUsize,
+}
+
+impl Waiters {
+    pub(crate) fn register(&self, waker: &Waker) {
+        let mut list = self.list.lock().unwrap();
+        if !list.iter().any(|w| w.will_wake(waker)) {
+            list.push_back(waker.clone());
+        }
+        self.count.store(list.len(), Ordering::SeqCst);
+    }
+    // return whether the waker was still parked
+    pub(crate) fn remove(&self, waker: &Waker) -> bool {
+        if self.count.load(Ordering::SeqCst) == 0 {
+            return false;
+        }
+        let mut list = self.list.lock().unwrap();
+        let len = list.len();
+        list.retain(|w| !w.will_wake(waker));
+        self.count.store(list.len(), Ordering::SeqCst);
+        len != list.len()
+    }
+    pub(crate) fn wake(&self, n: usize) {
+        if n == 0 || self.count.load(Ordering::SeqCst) == 0 {
+            return;
+        }
+        let mut list = self.list.lock().unwrap();
+        let n = n.min(list.len());
+        let wakers = list.drain(..n).collect::<Vec<_>>();
+        self.count.store(list.len(), Ordering::SeqCst);
+        drop(list);
+        for w in wakers {
+            w.wake();
+        }
+    }
+    pub(crate) fn wake_one(&self) {
+        if self.count.load(Ordering::SeqCst) == 0 {
+            return;
+        }
+        let mut list = self.list.lock().unwrap();
+        let waker = list.pop_front();
+        self.count.store(list.len(), Ordering::SeqCst);
+        drop(list);
+        if let Some(w) = waker {
+            w.wake();
+        }
+    }
+    pub(crate) fn wake_all(&self) {
+        self.wake(usize::MAX)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Push, RingBuffer, RingQueue};
+    use std::sync::atomic::Ordering;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_ring_buffer() {
+        let ring = RingBuffer::<usize>::new(1);
+        assert!(ring.push(1, 1).is_ok());
+        assert_eq!(ring.push(2, 1), Err(2));
+        assert_eq!(ring.pop(), Some(1));
+        assert_eq!(ring.pop(), None);
+
+        // grow past the initial slots and keep the order
+        let ring = RingBuffer::<usize>::new(usize::MAX);
+        for i in 0..5000 {
+            ring.push(i, usize::MAX).unwrap();
+            if i % 4 == 0 {
+                assert_eq!(ring.pop(), Some(i / 4));
+            }
+        }
+        assert_eq!(ring.len(), 5000 - 1250);
+        for i in 1250..5000 {
+            assert_eq!(ring.pop(), Some(i));
+        }
+        assert!(ring.is_empty());
+
+        // the capacity counts the items left in the sealed queue
+        let ring = RingBuffer::<usize>::new(2);
+        for i in 0..3 {
+            ring.push(i, 3).unwrap();
+        }
+        assert_eq!(ring.push(3, 3), Err(3));
+        assert_eq!(ring.pop(), Some(0));
+        ring.push(3, 3).unwrap();
+        assert_eq!(ring.len(), 3);
+
+        // grow while other threads push and pop
+        let ring = Arc::new(RingBuffer::<usize>::new(usize::MAX));
+        let workers = (0..4)
+            .map(|n| {
+                let ring = ring.clone();
+                std::thread::spawn(move || {
+                    let mut got = vec![];
+                    for i in 0..10_000 {
+                        ring.push(n * 10_000 + i, usize::MAX).unwrap();
+                        if i % 3 == 0 {
+                            got.extend(ring.pop());
+                        }
+                    }
+                    got
+                })
+            })
+            .collect::<Vec<_>>();
+        let mut list = workers
+            .into_iter()
+            .flat_map(|w| w.join().unwrap())
+            .collect::<Vec<_>>();
+        while let Some(i) = ring.pop() {
+            list.push(i);
+        }
+        list.sort();
+        assert_eq!(list, (0..40_000).collect::<Vec<_>>());
+
+        // positions past u32::MAX neither seal nor grow the queue
+        let queue = RingQueue::new(4, u32::MAX as u64 - 2);
+        for i in 0..100 {
+            assert!(matches!(
+                queue.push(i, 4, || queue.dequeue_pos.load(Ordering::SeqCst)),
+                Push::Ok
+            ));
+            assert_eq!(queue.pop(), Some(i));
+        }
+        assert!(!queue.is_drained());
+    }
+}
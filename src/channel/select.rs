@@ -0,0 +1,218 @@
+use crate::channel::{ChannelError, ChannelResult, Receiver, RecvError, RecvFuture};
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll, Waker};
+use std::time::Duration;
+
+/// 同时等待多个通道，返回第一个就绪通道的(index, item)，其他通道的数据不会丢失
+/// fair模式下轮流从每个通道开始检查，biased模式下总是从第一个开始
+/// Select持有Receiver，原来的Receiver全部drop后通道也不会被关闭
+pub struct Select<T> {
+    chans: Vec<Receiver<T>>,
+    biased: bool,
+    timeout: Option<Duration>,
+    start: usize,
+}
+
+impl<T> Default for Select<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> Select<T> {
+    pub fn new() -> Self {
+        Self {
+            chans: vec![],
+            biased: false,
+            timeout: None,
+            start: 0,
+        }
+    }
+    pub fn recv(mut self, receiver: &Receiver<T>) -> Self {
+        self.chans.push(receiver.clone());
+        self
+    }
+    pub fn recv_future(mut self, future: RecvFuture<T>) -> Self {
+        self.chans.push(Receiver::from(Arc::new(future.into_channel())));
+        self
+    }
+    pub fn biased(mut self) -> Self {
+        self.biased = true;
+        self
+    }
+    pub fn fair(mut self) -> Self {
+        self.biased = false;
+        self
+    }
+    // select returns RecvError::TIMEOUT if no channel is ready within timeout
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+    pub fn len(&self) -> usize {
+        self.chans.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.chans.is_empty()
+    }
+    pub fn try_select(&mut self) -> ChannelResult<(usize, T), RecvError> {
+        self.poll_items(None)
+    }
+    pub fn select(&mut self) -> SelectFuture<'_, T> {
+        SelectFuture {
+            select: self,
+            waker: None,
+            sleep: None,
+        }
+    }
+    // return CLOSED only if all channels are closed and empty
+    fn poll_items(&mut self, waker: Option<&Waker>) -> ChannelResult<(usize, T), RecvError> {
+        let n = self.chans.len();
+        if n == 0 {
+            return RecvError::CLOSED.into_err();
+        }
+        let start = if self.biased { 0 } else { self.start % n };
+        let mut closed = 0;
+        for k in 0..n {
+            let i = (start + k) % n;
+            match self.chans[i].channel()._try_recv(waker) {
+                Ok(t) => {
+                    self.start = i + 1;
+                    return Ok((i, t));
+                }
+                Err(RecvError::EMPTY) => {}
+                Err(RecvError::CLOSED) => closed += 1,
+                Err(e) => return e.into_err(),
+            }
+        }
+        if closed == n {
+            return RecvError::CLOSED.into_err();
+        }
+        RecvError::EMPTY.into_err()
+    }
+    fn cancel_waker(&self, waker: &Waker) {
+        for chan in self.chans.iter() {
+            chan.channel().cancel_recv_waker(waker);
+        }
+    }
+}
+
+pub struct SelectFuture<'a, T> {
+    select: &'a mut Select<T>,
+    waker: Option<Waker>,
+    sleep: Option<Pin<Box<tokio::time::Sleep>>>,
+}
+
+impl<T> SelectFuture<'_, T> {
+    fn complete<O>(&mut self, res: O) -> Poll<O> {
+        if let Some(w) = self.waker.take() {
+            self.select.cancel_waker(&w);
+        }
+        Poll::Ready(res)
+    }
+}
+
+impl<T> Future for SelectFuture<'_, T> {
+    type Output = ChannelResult<(usize, T), RecvError>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.get_mut();
+        if this.sleep.is_none() {
+            if let Some(d) = this.select.timeout {
+                this.sleep = Some(Box::pin(tokio::time::sleep(d)));
+            }
+        }
+        if let Some(w) = this.waker.as_ref() {
+            if !w.will_wake(cx.waker()) {
+                let w = this.waker.take().unwrap();
+                this.select.cancel_waker(&w);
+            }
+        }
+        match this.select.poll_items(Some(cx.waker())) {
+            Err(RecvError::EMPTY) => {}
+            res => return this.complete(res),
+        }
+        this.waker = Some(cx.waker().clone());
+        if let Some(sleep) = this.sleep.as_mut() {
+            if sleep.as_mut().poll(cx).is_ready() {
+                return this.complete(RecvError::TIMEOUT.into_err());
+            }
+        }
+        Poll::Pending
+    }
+}
+
+impl<T> Drop for SelectFuture<'_, T> {
+    fn drop(&mut self) {
+        if let Some(w) = self.waker.take() {
+            self.select.cancel_waker(&w);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::channel::{Channel, RecvError, Select, SendError};
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_select() {
+        let (s1, r1) = Channel::<usize>::new(10);
+        let (s2, r2) = Channel::<usize>::new(10);
+        let mut select = Select::new().recv(&r1).recv_future(r2.recv());
+
+        let sender = s2.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            sender.send(100).await.unwrap();
+        });
+        assert_eq!(select.select().await, Ok((1, 100)));
+
+        for i in 0..3 {
+            s1.try_send(i).unwrap();
+            s2.try_send(i + 10).unwrap();
+        }
+        // fair: take from each channel in turn
+        let mut list = vec![];
+        for _ in 0..4 {
+            list.push(select.select().await.unwrap());
+        }
+        assert_eq!(list, vec![(0, 0), (1, 10), (0, 1), (1, 11)]);
+
+        let mut biased = Select::new().recv(&r1).recv(&r2).biased();
+        assert_eq!(biased.try_select(), Ok((0, 2)));
+        assert_eq!(biased.try_select(), Ok((1, 12)));
+
+        let mut select = Select::new()
+            .recv(&r1)
+            .recv(&r2)
+            .timeout(Duration::from_millis(50));
+        assert_eq!(select.select().await, Err(RecvError::TIMEOUT));
+
+        // the waker of select is removed, a plain receiver is still woken
+        let receiver = r1.clone();
+        let wait = tokio::spawn(async move { receiver.recv().await });
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        s1.send(7).await.unwrap();
+        assert_eq!(wait.await.unwrap(), Ok(7));
+
+        s1.close();
+        s2.close();
+        assert_eq!(select.select().await, Err(RecvError::CLOSED));
+
+        // select keeps the channels open after the receivers are dropped
+        let (s1, r1) = Channel::<usize>::new(10);
+        let (s2, r2) = Channel::<usize>::new(10);
+        let mut select = Select::new().recv(&r1).recv_future(r2.recv());
+        drop(r1);
+        drop(r2);
+        assert!(!s1.is_closed());
+        s2.try_send(1).unwrap();
+        assert_eq!(select.select().await, Ok((1, 1)));
+        drop(select);
+        assert!(s1.is_closed());
+        assert_eq!(s2.try_send(2), Err(SendError::CLOSED(2)));
+    }
+}
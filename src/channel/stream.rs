@@ -0,0 +1,147 @@
+use crate::channel::{Receiver, RecvError, SendError, Sender};
+use futures::{Sink, Stream};
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+// the stream is over when the channel is closed and empty
+impl<T> Stream for Receiver<T> {
+    type Item = T;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        match self.get_mut().poll_recv(cx) {
+            Ok(t) => Poll::Ready(Some(t)),
+            Err(RecvError::EMPTY) => Poll::Pending,
+            Err(_) => Poll::Ready(None),
+        }
+    }
+}
+
+// the error gives back the item if it was not sent
+// each sender buffers at most one item, so backpressure is kept,
+// flush or close the sink before dropping it, a buffered item is dropped if the channel is full
+impl<T> Sink<T> for Sender<T> {
+    type Error = SendError<Option<T>>;
+
+    fn poll_ready(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        let this = self.get_mut();
+        match this.poll_send_pending(cx) {
+            Poll::Ready(Ok(_)) => {}
+            other => return other,
+        }
+        if this.is_released() || this.channel().is_closed() {
+            return Poll::Ready(Err(SendError::CLOSED(None)));
+        }
+        Poll::Ready(Ok(()))
+    }
+
+    fn start_send(self: Pin<&mut Self>, item: T) -> Result<(), Self::Error> {
+        self.get_mut().set_pending(item)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.get_mut().poll_send_pending(cx)
+    }
+
+    // flush and give up this sender, the channel is closed for sending when the last one is gone
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        let this = self.get_mut();
+        match this.poll_send_pending(cx) {
+            Poll::Ready(Ok(_)) => {}
+            other => return other,
+        }
+        this.release();
+        Poll::Ready(Ok(()))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::channel::{Channel, SendError};
+    use futures::{SinkExt, StreamExt};
+    use std::future::Future;
+    use std::pin::Pin;
+    use std::sync::Arc;
+    use std::task::{Context, Poll, Wake, Waker};
+    use std::time::Duration;
+
+    struct NoopWake;
+
+    impl Wake for NoopWake {
+        fn wake(self: Arc<Self>) {}
+    }
+
+    // poll with a waker of its own, so it is not merged with another parked waker
+    fn poll_once<F: Future + Unpin>(fut: &mut F) -> Poll<F::Output> {
+        let waker = Waker::from(Arc::new(NoopWake));
+        Pin::new(fut).poll(&mut Context::from_waker(&waker))
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_stream_sink() {
+        let (sender, receiver) = Channel::<usize>::new(2);
+        let (out_sender, out_receiver) = Channel::<usize>::new(2);
+        let producer = tokio::spawn(async move {
+            let mut sender = sender;
+            let mut source = futures::stream::iter((0..100).map(Ok));
+            sender.send_all(&mut source).await.unwrap();
+            sender.close();
+        });
+        let worker = tokio::spawn(async move {
+            receiver
+                .map(|i| async move { i * 2 })
+                .buffer_unordered(4)
+                .map(Ok)
+                .forward(out_sender)
+                .await
+                .unwrap();
+        });
+        let mut list = out_receiver.collect::<Vec<_>>().await;
+        producer.await.unwrap();
+        worker.await.unwrap();
+        list.sort();
+        assert_eq!(list, (0..100).map(|i| i * 2).collect::<Vec<_>>());
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_stream_with_recv() {
+        let (sender, mut stream) = Channel::<usize>::new(4);
+        let receiver = stream.clone();
+        let mut recv = Box::pin(receiver.recv());
+        assert!(poll_once(&mut recv).is_pending());
+        assert!(poll_once(&mut stream.next()).is_pending());
+        // the recv is woken, but the stream takes the item first
+        sender.try_send(0).unwrap();
+        assert_eq!(poll_once(&mut stream.next()), Poll::Ready(Some(0)));
+        // the waker of the stream is gone, so the next item wakes the recv
+        let waiting = tokio::spawn(recv);
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        sender.try_send(1).unwrap();
+        let res = tokio::time::timeout(Duration::from_secs(1), waiting).await;
+        assert_eq!(res.unwrap().unwrap(), Ok(1));
+
+        // a dropped stream passes its notification on
+        let receiver = stream.clone();
+        assert!(poll_once(&mut stream.next()).is_pending());
+        let waiting = tokio::spawn(async move { receiver.recv().await });
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        sender.try_send(2).unwrap();
+        drop(stream);
+        let res = tokio::time::timeout(Duration::from_secs(1), waiting).await;
+        assert_eq!(res.unwrap().unwrap(), Ok(2));
+    }
+
+    #[tokio::test]
+    async fn test_sink_close() {
+        let (sender, receiver) = Channel::<usize>::new(4);
+        let mut sink = sender.clone();
+        sink.send(1).await.unwrap();
+        SinkExt::close(&mut sink).await.unwrap();
+        // only this handle is given up
+        assert!(!receiver.is_closed());
+        assert_eq!(sink.feed(2).await, Err(SendError::CLOSED(None)));
+        sender.send(3).await.unwrap();
+        drop(sink);
+        drop(sender);
+        assert_eq!(receiver.collect::<Vec<_>>().await, vec![1, 3]);
+    }
+}
@@ -0,0 +1,229 @@
+use crate::channel::{ChannelError, ChannelResult, RecvError, SendError};
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, RwLock, RwLockReadGuard};
+use std::task::{Context, Poll, Waker};
+
+/// 观察通道
+/// 只保存最新的值，接收方可以随时读取当前值，也可以等待值发生变化
+#[derive(Debug)]
+pub struct Watch<T> {
+    status: AtomicBool,
+    value: RwLock<T>,
+    // increased on every send
+    version: AtomicU64,
+    receiver_waker: Mutex<Vec<Waker>>,
+}
+
+#[derive(Debug)]
+pub struct WatchSender<T> {
+    chan: Arc<Watch<T>>,
+}
+
+#[derive(Debug)]
+pub struct WatchReceiver<T> {
+    chan: Arc<Watch<T>>,
+    // the version seen last time
+    seen: u64,
+}
+
+// the clone has seen the same version
+impl<T> Clone for WatchReceiver<T> {
+    fn clone(&self) -> Self {
+        Self {
+            chan: self.chan.clone(),
+            seen: self.seen,
+        }
+    }
+}
+
+impl<T> Watch<T> {
+    #[allow(clippy::new_ret_no_self)]
+    pub fn new(init: T) -> (WatchSender<T>, WatchReceiver<T>) {
+        let chan = Arc::new(Watch {
+            status: AtomicBool::new(true),
+            value: RwLock::new(init),
+            version: AtomicU64::new(0),
+            receiver_waker: Mutex::new(Vec::new()),
+        });
+        let receiver = WatchReceiver {
+            chan: chan.clone(),
+            seen: 0,
+        };
+        (WatchSender { chan }, receiver)
+    }
+    fn wake_all(&self) {
+        let mut list = self.receiver_waker.lock().unwrap();
+        for i in list.drain(..) {
+            i.wake();
+        }
+    }
+    fn close(&self) {
+        self.status.store(false, Ordering::SeqCst);
+        self.wake_all();
+    }
+    fn is_closed(&self) -> bool {
+        !self.status.load(Ordering::SeqCst)
+    }
+}
+
+impl<T> WatchSender<T> {
+    pub fn send(&self, value: T) -> ChannelResult<(), SendError<T>> {
+        self.send_modify_inner(value, |old, new| *old = new)
+    }
+    // modify the current value in place and notify receivers
+    pub fn send_modify(&self, f: impl FnOnce(&mut T)) -> ChannelResult<(), SendError<()>> {
+        self.send_modify_inner((), |old, _| f(old))
+    }
+    fn send_modify_inner<V>(
+        &self,
+        value: V,
+        f: impl FnOnce(&mut T, V),
+    ) -> ChannelResult<(), SendError<V>> {
+        if self.chan.is_closed() {
+            return SendError::CLOSED(value).into_err();
+        }
+        {
+            let mut old = match self.chan.value.write() {
+                Ok(o) => o,
+                Err(e) => return SendError::UNKNOWN(value, e.to_string()).into_err(),
+            };
+            f(&mut old, value);
+            self.chan.version.fetch_add(1, Ordering::SeqCst);
+        }
+        self.chan.wake_all();
+        Ok(())
+    }
+    pub fn borrow(&self) -> RwLockReadGuard<'_, T> {
+        self.chan.value.read().unwrap()
+    }
+    // the new receiver sees the current value as not changed
+    pub fn subscribe(&self) -> WatchReceiver<T> {
+        WatchReceiver {
+            chan: self.chan.clone(),
+            seen: self.chan.version.load(Ordering::SeqCst),
+        }
+    }
+    pub fn close(&self) {
+        self.chan.close()
+    }
+    pub fn is_closed(&self) -> bool {
+        self.chan.is_closed()
+    }
+}
+
+// dropping the sender closes the channel
+impl<T> Drop for WatchSender<T> {
+    fn drop(&mut self) {
+        self.chan.close()
+    }
+}
+
+impl<T> WatchReceiver<T> {
+    pub fn borrow(&self) -> RwLockReadGuard<'_, T> {
+        self.chan.value.read().unwrap()
+    }
+    // borrow the current value and mark it as seen
+    pub fn borrow_and_update(&mut self) -> RwLockReadGuard<'_, T> {
+        let guard = self.chan.value.read().unwrap();
+        self.seen = self.chan.version.load(Ordering::SeqCst);
+        guard
+    }
+    pub fn has_changed(&self) -> bool {
+        self.chan.version.load(Ordering::SeqCst) != self.seen
+    }
+    // wait until a new value is sent, return CLOSED if the sender is closed
+    pub fn changed(&mut self) -> WatchChangedFuture<'_, T> {
+        WatchChangedFuture {
+            receiver: self,
+            waker: None,
+        }
+    }
+    pub fn is_closed(&self) -> bool {
+        self.chan.is_closed()
+    }
+    fn poll_changed(&mut self, waker: &Waker) -> ChannelResult<(), RecvError> {
+        let mut list = match self.chan.receiver_waker.lock() {
+            Ok(o) => o,
+            Err(e) => return RecvError::UNKNOWN(e.to_string()).into_err(),
+        };
+        let version = self.chan.version.load(Ordering::SeqCst);
+        if version != self.seen {
+            self.seen = version;
+            return Ok(());
+        }
+        if self.chan.is_closed() {
+            return RecvError::CLOSED.into_err();
+        }
+        if !list.iter().any(|i| i.will_wake(waker)) {
+            list.push(waker.clone());
+        }
+        RecvError::EMPTY.into_err()
+    }
+}
+
+pub struct WatchChangedFuture<'a, T> {
+    receiver: &'a mut WatchReceiver<T>,
+    waker: Option<Waker>,
+}
+
+impl<T> Future for WatchChangedFuture<'_, T> {
+    type Output = ChannelResult<(), RecvError>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        match self.receiver.poll_changed(cx.waker()) {
+            Err(RecvError::EMPTY) => {
+                self.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+            res => {
+                self.waker = None;
+                Poll::Ready(res)
+            }
+        }
+    }
+}
+
+impl<T> Drop for WatchChangedFuture<'_, T> {
+    fn drop(&mut self) {
+        if let Some(w) = self.waker.take() {
+            if let Ok(mut list) = self.receiver.chan.receiver_waker.lock() {
+                list.retain(|i| !i.will_wake(&w));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::channel::{RecvError, Watch};
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_watch() {
+        let (sender, mut receiver) = Watch::new(0usize);
+        let mut late = receiver.clone();
+        assert_eq!(*receiver.borrow(), 0);
+        assert!(!receiver.has_changed());
+
+        let handle = tokio::spawn(async move {
+            let mut list = vec![];
+            while late.changed().await.is_ok() {
+                list.push(*late.borrow_and_update());
+            }
+            list
+        });
+        for i in 1..=3 {
+            sender.send(i).unwrap();
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        sender.send_modify(|v| *v *= 10).unwrap();
+        assert!(receiver.has_changed());
+        assert_eq!(*receiver.borrow_and_update(), 30);
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        drop(sender);
+        assert_eq!(receiver.changed().await, Err(RecvError::CLOSED));
+        assert_eq!(handle.await.unwrap(), vec![1, 2, 3, 30]);
+    }
+}
@@ -0,0 +1,224 @@
+use crate::global;
+use crate::ptr::type_id;
+use std::any::Any;
+use std::collections::HashMap;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, RwLock};
+use tokio::sync::OnceCell;
+
+type Value = Arc<dyn Any + Send + Sync>;
+type ProviderFuture = Pin<Box<dyn Future<Output = anyhow::Result<Value>> + Send>>;
+type Factory = Arc<dyn Fn(Resolver) -> ProviderFuture + Send + Sync>;
+
+#[derive(Clone)]
+enum Provider {
+    Singleton(Value),
+    Lazy(Factory, Arc<OnceCell<Value>>),
+    Transient(Factory),
+}
+
+#[derive(Default)]
+struct ContainerInner {
+    providers: RwLock<HashMap<u64, Provider>>,
+}
+
+/// 依赖注入容器
+/// 按类型注册provider：singleton直接放入实例，lazy第一次获取时创建，transient每次获取都新建
+#[derive(Default, Clone)]
+pub struct Container {
+    inner: Arc<ContainerInner>,
+}
+
+/// 解析依赖时传给provider，记录解析链路用于循环检测
+#[derive(Clone)]
+pub struct Resolver {
+    inner: Arc<ContainerInner>,
+    stack: Vec<(u64, &'static str)>,
+}
+
+fn factory<T, F, Fut>(f: F) -> Factory
+where
+    T: Any + Send + Sync,
+    F: Fn(Resolver) -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = anyhow::Result<T>> + Send + 'static,
+{
+    Arc::new(move |r| {
+        let fut = f(r);
+        Box::pin(async move {
+            let t: Value = Arc::new(fut.await?);
+            Ok(t)
+        })
+    })
+}
+
+impl Container {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// 全局默认容器，存放在common::global中
+    /// 被global::remove移除后再次调用会重新创建
+    pub fn global() -> Container {
+        loop {
+            if let Some(c) = global::try_get::<Container>() {
+                return c.as_ref().clone();
+            }
+            // init keeps the container if another caller has just set it
+            global::init(Container::new());
+        }
+    }
+    fn register<T: Any>(&self, provider: Provider) {
+        self.inner
+            .providers
+            .write()
+            .unwrap()
+            .insert(type_id::<T>(), provider);
+    }
+    pub fn provide_singleton<T: Any + Send + Sync>(&self, t: T) {
+        self.register::<T>(Provider::Singleton(Arc::new(t)))
+    }
+    pub fn provide_lazy<T, F, Fut>(&self, f: F)
+    where
+        T: Any + Send + Sync,
+        F: Fn(Resolver) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = anyhow::Result<T>> + Send + 'static,
+    {
+        self.register::<T>(Provider::Lazy(factory(f), Arc::new(OnceCell::new())))
+    }
+    pub fn provide_transient<T, F, Fut>(&self, f: F)
+    where
+        T: Any + Send + Sync,
+        F: Fn(Resolver) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = anyhow::Result<T>> + Send + 'static,
+    {
+        self.register::<T>(Provider::Transient(factory(f)))
+    }
+    pub fn contains<T: Any>(&self) -> bool {
+        self.inner
+            .providers
+            .read()
+            .unwrap()
+            .contains_key(&type_id::<T>())
+    }
+    pub fn remove<T: Any>(&self) -> bool {
+        self.inner
+            .providers
+            .write()
+            .unwrap()
+            .remove(&type_id::<T>())
+            .is_some()
+    }
+    pub fn resolver(&self) -> Resolver {
+        Resolver {
+            inner: self.inner.clone(),
+            stack: vec![],
+        }
+    }
+    pub async fn resolve<T: Any + Send + Sync>(&self) -> anyhow::Result<Arc<T>> {
+        self.resolver().resolve::<T>().await
+    }
+}
+
+impl Resolver {
+    fn cycle_error(&self, name: &'static str) -> anyhow::Error {
+        let mut path = self.stack.iter().map(|(_, n)| *n).collect::<Vec<_>>();
+        path.push(name);
+        anyhow::anyhow!("Container: dependency cycle [{}]", path.join(" -> "))
+    }
+    pub async fn resolve<T: Any + Send + Sync>(&self) -> anyhow::Result<Arc<T>> {
+        let id = type_id::<T>();
+        let name = std::any::type_name::<T>();
+        if self.stack.iter().any(|(i, _)| *i == id) {
+            return Err(self.cycle_error(name));
+        }
+        let provider = self.inner.providers.read().unwrap().get(&id).cloned();
+        let provider = match provider {
+            Some(s) => s,
+            None => return Err(anyhow::anyhow!("Container: provider[{}] not found", name)),
+        };
+        let mut next = self.clone();
+        next.stack.push((id, name));
+        let val = match provider {
+            Provider::Singleton(val) => val,
+            Provider::Lazy(f, cell) => cell.get_or_try_init(|| f(next)).await?.clone(),
+            Provider::Transient(f) => f(next).await?,
+        };
+        match val.downcast::<T>() {
+            Ok(o) => Ok(o),
+            Err(_) => Err(anyhow::anyhow!(
+                "Container: provider[{}] type mismatch",
+                name
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Container;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    struct Config {
+        url: String,
+    }
+    struct Db {
+        url: String,
+    }
+    struct Service {
+        db: Arc<Db>,
+    }
+
+    #[tokio::test]
+    async fn test_container() {
+        let created = Arc::new(AtomicUsize::new(0));
+        let c = Container::new();
+        c.provide_singleton(Config {
+            url: "mysql://localhost".into(),
+        });
+        let count = created.clone();
+        c.provide_lazy(move |r| {
+            let count = count.clone();
+            async move {
+                let cfg = r.resolve::<Config>().await?;
+                count.fetch_add(1, Ordering::Relaxed);
+                Ok(Db {
+                    url: cfg.url.clone(),
+                })
+            }
+        });
+        c.provide_transient(|r| async move {
+            let db = r.resolve::<Db>().await?;
+            Ok(Service { db })
+        });
+
+        let s1 = c.resolve::<Service>().await.unwrap();
+        let s2 = c.resolve::<Service>().await.unwrap();
+        assert!(!Arc::ptr_eq(&s1, &s2));
+        assert!(Arc::ptr_eq(&s1.db, &s2.db));
+        assert_eq!(s1.db.url, "mysql://localhost");
+        assert_eq!(created.load(Ordering::Relaxed), 1);
+        assert!(c.resolve::<String>().await.is_err());
+    }
+
+    struct A;
+    struct B;
+
+    #[tokio::test]
+    async fn test_container_cycle() {
+        // the global container comes back after it is removed
+        Container::global();
+        assert!(crate::global::remove::<Container>().is_some());
+        let c = Container::global();
+        c.provide_lazy(|r| async move {
+            r.resolve::<B>().await?;
+            Ok(A)
+        });
+        c.provide_transient(|r| async move {
+            r.resolve::<A>().await?;
+            Ok(B)
+        });
+        let err = Container::global().resolve::<A>().await.err().unwrap();
+        assert!(err.to_string().contains("dependency cycle"), "{}", err);
+    }
+}
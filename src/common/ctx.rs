@@ -1,27 +1,214 @@
 use crate::AsBytes;
-use std::any::Any;
+use std::any::{Any, TypeId};
+use std::borrow::Cow;
 use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
 use std::future::Future;
+use std::marker::PhantomData;
 use std::ops::{Deref, DerefMut};
-use std::sync::atomic::{AtomicIsize, AtomicUsize, Ordering};
-use std::sync::{Arc, RwLock};
-use std::time::Duration;
+use std::sync::atomic::{AtomicIsize, AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, RwLock, Weak};
+use std::time::{Duration, Instant};
 use tokio::sync::Notify;
 
+/// the deadline of ctx is reached, use `err.is::<DeadlineExceeded>()` to check
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DeadlineExceeded;
+
+impl Display for DeadlineExceeded {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "ctx deadline exceeded")
+    }
+}
+
+impl std::error::Error for DeadlineExceeded {}
+
+/// the ctx is stopped before the future is over, use `err.is::<Cancelled>()` to check
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cancelled;
+
+impl Display for Cancelled {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "ctx cancelled")
+    }
+}
+
+impl std::error::Error for Cancelled {}
+
+type TypedMap = HashMap<(TypeId, &'static str), Box<dyn Any + Send + Sync>>;
+
+/// key of ctx value with the value type, keys with the same name but different types never collide
+/// ```ignore
+/// const USER: TypedKey<String> = TypedKey::new("user");
+/// USER.insert(&ctx, "wd".to_string());
+/// ```
+pub struct TypedKey<V> {
+    name: &'static str,
+    _marker: PhantomData<fn() -> V>,
+}
+
+impl<V> Clone for TypedKey<V> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+impl<V> Copy for TypedKey<V> {}
+
+impl<V: Any + Send + Sync> TypedKey<V> {
+    pub const fn new(name: &'static str) -> Self {
+        Self {
+            name,
+            _marker: PhantomData,
+        }
+    }
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+    fn key(&self) -> (TypeId, &'static str) {
+        (TypeId::of::<V>(), self.name)
+    }
+    pub fn insert(&self, ctx: &Ctx, val: V) -> Option<V> {
+        let old = ctx.typed.write().unwrap().insert(self.key(), Box::new(val));
+        old?.downcast::<V>().ok().map(|v| *v)
+    }
+    // lookup falls back to the parent if the key is not in ctx
+    pub fn get<O>(&self, ctx: &Ctx, handle: impl FnOnce(Option<&V>) -> O) -> O {
+        let reader = ctx.typed.read().unwrap();
+        if let Some(a) = reader.get(&self.key()) {
+            return handle(a.downcast_ref::<V>());
+        }
+        drop(reader);
+        match ctx.parent {
+            Some(ref parent) => self.get(parent, handle),
+            None => handle(None),
+        }
+    }
+    pub fn get_cloned(&self, ctx: &Ctx) -> Option<V>
+    where
+        V: Clone,
+    {
+        self.get(ctx, |v| v.cloned())
+    }
+    pub fn remove(&self, ctx: &Ctx) -> Option<V> {
+        let old = ctx.typed.write().unwrap().remove(&self.key());
+        old?.downcast::<V>().ok().map(|v| *v)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SubtaskStatus {
+    Running,
+    // ctx is stopped, but the subtask is not over yet
+    Stopping,
+    // the timeout of the subtask or the deadline of ctx is exceeded, but the subtask is not over yet
+    Overdue,
+}
+
+#[derive(Debug, Clone)]
+pub struct SubtaskInfo {
+    pub id: u64,
+    pub name: Cow<'static, str>,
+    pub elapsed: Duration,
+    pub status: SubtaskStatus,
+}
+
+struct SubtaskEntry {
+    name: Cow<'static, str>,
+    // the signal of the ctx that runs the subtask
+    signal: Arc<CtxSignal>,
+    start: Instant,
+    // the earlier of the subtask's timeout and the deadline of ctx
+    deadline: Option<Instant>,
+}
+
+type SubtaskMap = HashMap<u64, SubtaskEntry>;
+
+// done the subtask even if the future is dropped
+struct SubtaskGuard {
+    ctx: Ctx,
+    id: u64,
+}
+
+impl SubtaskGuard {
+    fn new(ctx: &Ctx, name: Cow<'static, str>, timeout: Option<Duration>) -> Self {
+        static ID: AtomicU64 = AtomicU64::new(1);
+        let id = ID.fetch_add(1, Ordering::Relaxed);
+        let start = Instant::now();
+        let deadline = match (timeout.map(|t| start + t), ctx.deadline) {
+            (Some(a), Some(b)) => Some(a.min(b)),
+            (a, b) => a.or(b),
+        };
+        // the ancestors list the subtask as well, so they can wait for it
+        for c in ctx.counting() {
+            let entry = SubtaskEntry {
+                name: name.clone(),
+                signal: ctx.signal.clone(),
+                start,
+                deadline,
+            };
+            c.tasks.lock().unwrap().insert(id, entry);
+        }
+        ctx.add_task(1);
+        Self {
+            ctx: ctx.clone(),
+            id,
+        }
+    }
+}
+
+impl Drop for SubtaskGuard {
+    fn drop(&mut self) {
+        for c in self.ctx.counting() {
+            c.tasks.lock().unwrap().remove(&self.id);
+        }
+        self.ctx.done_task();
+    }
+}
+
+#[derive(Default)]
+struct CtxSignal {
+    status: AtomicUsize,
+    notify: Notify,
+    children: Mutex<Vec<Weak<CtxSignal>>>,
+}
+
+impl CtxSignal {
+    fn stop(&self) {
+        self.status.fetch_add(1, Ordering::Release);
+        self.notify.notify_waiters();
+        let children = std::mem::take(self.children.lock().unwrap().deref_mut());
+        for child in children.iter().filter_map(|c| c.upgrade()) {
+            child.stop();
+        }
+    }
+    fn is_stop(&self) -> bool {
+        self.status.load(Ordering::Acquire) > 0
+    }
+}
+
 #[derive(Default)]
 pub struct Ctx {
-    status: Arc<AtomicUsize>,
+    signal: Arc<CtxSignal>,
     subtask: Arc<AtomicIsize>,
     map: Arc<RwLock<HashMap<Vec<u8>, Box<dyn Any + Send + Sync>>>>,
-    notify: Arc<Notify>,
+    typed: Arc<RwLock<TypedMap>>,
+    tasks: Arc<Mutex<SubtaskMap>>,
+    parent: Option<Arc<Ctx>>,
+    // the subtasks are not counted by the parent
+    detached: bool,
+    deadline: Option<Instant>,
 }
 impl Clone for Ctx {
     fn clone(&self) -> Self {
         Self {
-            status: self.status.clone(),
+            signal: self.signal.clone(),
             subtask: self.subtask.clone(),
             map: self.map.clone(),
-            notify: self.notify.clone(),
+            typed: self.typed.clone(),
+            tasks: self.tasks.clone(),
+            parent: self.parent.clone(),
+            detached: self.detached,
+            deadline: self.deadline,
         }
     }
 }
@@ -29,6 +216,85 @@ unsafe impl Send for Ctx {}
 unsafe impl Sync for Ctx {}
 
 impl Ctx {
+    // the child is stopped when self is stopped, but stopping the child does not affect self
+    #[allow(dead_code)]
+    pub fn child(&self) -> Ctx {
+        let signal = Arc::new(CtxSignal::default());
+        {
+            let mut children = self.signal.children.lock().unwrap();
+            children.retain(|c| c.strong_count() > 0);
+            children.push(Arc::downgrade(&signal));
+        }
+        if self.is_stop() {
+            signal.stop();
+        }
+        Ctx {
+            signal,
+            subtask: Arc::new(AtomicIsize::new(0)),
+            map: Arc::new(RwLock::new(HashMap::new())),
+            typed: Arc::new(RwLock::new(HashMap::new())),
+            tasks: Arc::new(Mutex::new(HashMap::new())),
+            parent: Some(Arc::new(self.clone())),
+            detached: false,
+            deadline: self.deadline,
+        }
+    }
+    // like child, but it is not stopped when self is stopped
+    #[allow(dead_code)]
+    pub fn detach(&self) -> Ctx {
+        Ctx {
+            signal: Arc::new(CtxSignal::default()),
+            subtask: Arc::new(AtomicIsize::new(0)),
+            map: Arc::new(RwLock::new(HashMap::new())),
+            typed: Arc::new(RwLock::new(HashMap::new())),
+            tasks: Arc::new(Mutex::new(HashMap::new())),
+            parent: Some(Arc::new(self.clone())),
+            detached: true,
+            deadline: self.deadline,
+        }
+    }
+    // self and the ancestors up to a detached ctx, they all count the subtasks of self
+    fn counting(&self) -> Vec<&Ctx> {
+        let mut list = vec![self];
+        let mut ctx = self;
+        while let (false, Some(p)) = (ctx.detached, ctx.parent.as_deref()) {
+            list.push(p);
+            ctx = p;
+        }
+        list
+    }
+    // child with deadline, the earlier of parent's deadline and the given one is used
+    #[allow(dead_code)]
+    pub fn with_deadline(&self, deadline: Instant) -> Ctx {
+        let mut child = self.child();
+        child.deadline = match self.deadline {
+            Some(d) if d < deadline => Some(d),
+            _ => Some(deadline),
+        };
+        child
+    }
+    #[allow(dead_code)]
+    pub fn with_timeout(&self, timeout: Duration) -> Ctx {
+        self.with_deadline(Instant::now() + timeout)
+    }
+    #[allow(dead_code)]
+    pub fn deadline(&self) -> Option<Instant> {
+        self.deadline
+    }
+    // None means no deadline
+    #[allow(dead_code)]
+    pub fn remaining(&self) -> Option<Duration> {
+        self.deadline
+            .map(|d| d.saturating_duration_since(Instant::now()))
+    }
+    #[allow(dead_code)]
+    pub fn is_deadline_exceeded(&self) -> bool {
+        self.remaining() == Some(Duration::ZERO)
+    }
+    #[allow(dead_code)]
+    pub fn parent(&self) -> Option<Ctx> {
+        self.parent.as_ref().map(|p| p.as_ref().clone())
+    }
     #[allow(dead_code)]
     pub fn insert<K: AsBytes, V: Any + Send + Sync>(
         &self,
@@ -42,38 +308,31 @@ impl Ctx {
     pub fn remove<K: AsBytes, V: Any>(&self, key: K) -> Option<V> {
         let key = key.as_byte();
         self.ref_inner_mut(|map| {
-            if let Some(s) = map.get(key) {
-                if s.downcast_ref::<V>().is_none() {
-                    return None;
-                }
-            } else {
+            if !map.get(key)?.is::<V>() {
                 return None;
             }
-            if let Some(s) = map.remove(key) {
-                let val = Box::into_raw(s) as *mut V;
-                unsafe {
-                    let a = Box::from_raw(val);
-                    return Some(*a);
-                }
-            }
-            None
+            map.remove(key)?.downcast::<V>().ok().map(|v| *v)
         })
     }
+    // lookup falls back to the parent if the key is not in self
     #[allow(dead_code)]
     pub fn ref_handle<K: AsBytes, V: Any, O>(
         &self,
         key: K,
         handle: impl FnOnce(Option<&V>) -> O,
     ) -> O {
-        let key = key.as_byte();
-        self.ref_inner(|map| {
-            let opt = map.get(key);
-            let res = match opt {
-                None => None,
-                Some(a) => a.downcast_ref::<V>(),
-            };
-            handle(res)
-        })
+        self.ref_handle_bytes(key.as_byte(), handle)
+    }
+    fn ref_handle_bytes<V: Any, O>(&self, key: &[u8], handle: impl FnOnce(Option<&V>) -> O) -> O {
+        let reader = self.map.read().unwrap();
+        if let Some(a) = reader.get(key) {
+            return handle(a.downcast_ref::<V>());
+        }
+        drop(reader);
+        match self.parent {
+            Some(ref parent) => parent.ref_handle_bytes(key, handle),
+            None => handle(None),
+        }
     }
     #[allow(dead_code)]
     pub fn ref_handle_mut<K: AsBytes, V: Any, O>(
@@ -109,40 +368,74 @@ impl Ctx {
     }
     #[allow(dead_code)]
     pub fn add_task(&self, count: isize) {
-        self.subtask.fetch_add(count, Ordering::Release);
+        for c in self.counting() {
+            c.subtask.fetch_add(count, Ordering::Release);
+        }
     }
     #[allow(dead_code)]
     pub fn done_task(&self) {
-        self.subtask.fetch_sub(1, Ordering::Release);
-        self.notify.notify_waiters();
+        for c in self.counting() {
+            c.subtask.fetch_sub(1, Ordering::Release);
+            c.signal.notify.notify_waiters();
+        }
+    }
+    // the subtasks of self and its children, a detached ctx counts its own
+    #[allow(dead_code)]
+    pub fn subtask_count(&self) -> isize {
+        self.subtask.load(Ordering::Acquire)
     }
-    // stop all task
+    // in-flight subtasks registered by exec_future/call/spawn, include those of the children,
+    // the longest running first
+    #[allow(dead_code)]
+    pub fn subtasks(&self) -> Vec<SubtaskInfo> {
+        let now = Instant::now();
+        let mut list = self
+            .tasks
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(id, entry)| SubtaskInfo {
+                id: *id,
+                name: entry.name.clone(),
+                elapsed: now.saturating_duration_since(entry.start),
+                status: match entry.deadline {
+                    _ if entry.signal.is_stop() => SubtaskStatus::Stopping,
+                    Some(d) if d <= now => SubtaskStatus::Overdue,
+                    _ => SubtaskStatus::Running,
+                },
+            })
+            .collect::<Vec<_>>();
+        list.sort_by_key(|t| std::cmp::Reverse(t.elapsed));
+        list
+    }
+    // stop all task, include all children
     #[allow(dead_code)]
     pub fn stop(&self) {
-        self.status.fetch_add(1, Ordering::Release);
-        self.notify.notify_waiters();
+        self.signal.stop();
     }
     #[allow(dead_code)]
     pub fn is_stop(&self) -> bool {
-        self.status.load(Ordering::Acquire) > 0
+        self.signal.is_stop()
     }
     #[allow(dead_code)]
     pub async fn wait_stop_status(&self) {
         loop {
+            let notified = self.signal.notify.notified();
             if self.is_stop() {
                 return;
             }
-            self.notify.notified().await;
+            notified.await;
         }
     }
     // if set timeout, and result is timeout, The subtask does not continue
     #[allow(dead_code)]
     pub async fn wait_all_subtask_over(&self) {
         loop {
+            let notified = self.signal.notify.notified();
             if self.subtask.load(Ordering::Acquire) <= 0 {
                 return;
             }
-            self.notify.notified().await;
+            notified.await;
         }
     }
     #[allow(dead_code)]
@@ -154,17 +447,83 @@ impl Ctx {
     where
         Fut: Future<Output = anyhow::Result<Out>> + Send + 'static,
     {
-        self.add_task(1);
-        let res = if let Some(d) = timeout {
-            match tokio::time::timeout(d, future).await {
-                Ok(o) => o,
-                Err(e) => Err(anyhow::Error::new(e)),
+        self.exec_named_future(std::any::type_name::<Fut>(), future, timeout)
+            .await
+    }
+    #[allow(dead_code)]
+    pub async fn exec_named_future<N, Fut, Out>(
+        self,
+        name: N,
+        future: Fut,
+        timeout: Option<Duration>,
+    ) -> anyhow::Result<Out>
+    where
+        N: Into<Cow<'static, str>>,
+        Fut: Future<Output = anyhow::Result<Out>> + Send + 'static,
+    {
+        let _guard = SubtaskGuard::new(&self, name.into(), timeout);
+        tokio::select! {
+            biased;
+            _ = self.wait_stop_status() => Err(anyhow::Error::new(Cancelled)),
+            res = self.timeout_future(future, timeout) => res,
+        }
+    }
+    // spawn a subtask, the task is aborted when ctx stop
+    #[allow(dead_code)]
+    pub fn spawn<Fut>(&self, future: Fut) -> tokio::task::JoinHandle<anyhow::Result<Fut::Output>>
+    where
+        Fut: Future + Send + 'static,
+        Fut::Output: Send + 'static,
+    {
+        self.spawn_named(std::any::type_name::<Fut>(), future)
+    }
+    #[allow(dead_code)]
+    pub fn spawn_named<N, Fut>(
+        &self,
+        name: N,
+        future: Fut,
+    ) -> tokio::task::JoinHandle<anyhow::Result<Fut::Output>>
+    where
+        N: Into<Cow<'static, str>>,
+        Fut: Future + Send + 'static,
+        Fut::Output: Send + 'static,
+    {
+        let guard = SubtaskGuard::new(self, name.into(), None);
+        tokio::spawn(async move {
+            let ctx = &guard.ctx;
+            tokio::select! {
+                biased;
+                _ = ctx.wait_stop_status() => Err(anyhow::Error::new(Cancelled)),
+                out = future => Ok(out),
             }
-        } else {
-            future.await
+        })
+    }
+    // run future with timeout, the timeout is clamped to the remaining time of the deadline
+    #[allow(dead_code)]
+    pub async fn timeout_future<Fut, Out>(
+        &self,
+        future: Fut,
+        timeout: Option<Duration>,
+    ) -> anyhow::Result<Out>
+    where
+        Fut: Future<Output = anyhow::Result<Out>>,
+    {
+        let (timeout, by_deadline) = match (timeout, self.remaining()) {
+            (_, Some(Duration::ZERO)) => return Err(anyhow::Error::new(DeadlineExceeded)),
+            (Some(t), Some(r)) if r < t => (Some(r), true),
+            (Some(t), _) => (Some(t), false),
+            (None, Some(r)) => (Some(r), true),
+            (None, None) => (None, false),
+        };
+        let d = match timeout {
+            Some(d) => d,
+            None => return future.await,
         };
-        self.done_task();
-        return res;
+        match tokio::time::timeout(d, future).await {
+            Ok(o) => o,
+            Err(_) if by_deadline => Err(anyhow::Error::new(DeadlineExceeded)),
+            Err(e) => Err(anyhow::Error::new(e)),
+        }
     }
     #[allow(dead_code)]
     pub async fn call_timeout<F, Fut, Out>(
@@ -175,9 +534,24 @@ impl Ctx {
     where
         Fut: Future<Output = anyhow::Result<Out>> + Send + 'static,
         F: FnOnce(Ctx) -> Fut,
+    {
+        self.call_named_timeout(std::any::type_name::<F>(), lambda, timeout)
+            .await
+    }
+    #[allow(dead_code)]
+    pub async fn call_named_timeout<N, F, Fut, Out>(
+        self,
+        name: N,
+        lambda: F,
+        timeout: Option<Duration>,
+    ) -> anyhow::Result<Out>
+    where
+        N: Into<Cow<'static, str>>,
+        Fut: Future<Output = anyhow::Result<Out>> + Send + 'static,
+        F: FnOnce(Ctx) -> Fut,
     {
         let future = lambda(self.clone());
-        self.exec_future(future, timeout).await
+        self.exec_named_future(name, future, timeout).await
     }
     #[allow(dead_code)]
     pub async fn call<F, Fut, Out>(self, lambda: F) -> anyhow::Result<Out>
@@ -187,11 +561,20 @@ impl Ctx {
     {
         self.call_timeout(lambda, None).await
     }
+    #[allow(dead_code)]
+    pub async fn call_named<N, F, Fut, Out>(self, name: N, lambda: F) -> anyhow::Result<Out>
+    where
+        N: Into<Cow<'static, str>>,
+        Fut: Future<Output = anyhow::Result<Out>> + Send + 'static,
+        F: FnOnce(Ctx) -> Fut,
+    {
+        self.call_named_timeout(name, lambda, None).await
+    }
 }
 
 #[cfg(test)]
 mod test {
-    use crate::common::ctx::Ctx;
+    use crate::common::ctx::{Cancelled, Ctx, DeadlineExceeded, SubtaskStatus, TypedKey};
     use std::time::Duration;
 
     #[tokio::test]
@@ -248,4 +631,199 @@ mod test {
         tokio::time::sleep(Duration::from_secs(1)).await;
         ctx.wait_all_subtask_over().await;
     }
+    #[tokio::test]
+    async fn test_child() {
+        let root = Ctx::default();
+        root.insert("user", "root".to_string());
+        let child = root.child();
+        let grandchild = child.child();
+        grandchild.insert("user", "grandchild".to_string());
+
+        let user = child.ref_handle("user", |x: Option<&String>| x.cloned());
+        assert_eq!(user.as_deref(), Some("root"));
+        let user = grandchild.ref_handle("user", |x: Option<&String>| x.cloned());
+        assert_eq!(user.as_deref(), Some("grandchild"));
+
+        child.stop();
+        assert!(child.is_stop());
+        assert!(grandchild.is_stop());
+        assert!(!root.is_stop());
+
+        let other = root.child();
+        let c = other.child();
+        let wait = tokio::spawn(async move { c.wait_stop_status().await });
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        root.stop();
+        tokio::time::timeout(Duration::from_secs(1), wait)
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(other.is_stop());
+        assert!(root.child().is_stop());
+        let detached = root.detach();
+        assert!(!detached.is_stop());
+        let user = detached.ref_handle("user", |x: Option<&String>| x.cloned());
+        assert_eq!(user.as_deref(), Some("root"));
+    }
+    #[tokio::test]
+    async fn test_child_subtasks() {
+        let root = Ctx::default();
+        let child = root.with_timeout(Duration::from_secs(10));
+        let _task = child.child().spawn_named("grandchild", async {
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        });
+        // a detached ctx counts its own subtasks
+        let detached = root.detach();
+        detached.add_task(1);
+        assert_eq!(root.subtask_count(), 1);
+        assert_eq!(child.subtask_count(), 1);
+        let list = root.subtasks();
+        assert_eq!(list.len(), 1);
+        assert_eq!(list[0].name, "grandchild");
+        assert_eq!(list[0].status, SubtaskStatus::Running);
+
+        tokio::time::timeout(Duration::from_secs(1), root.wait_all_subtask_over())
+            .await
+            .unwrap();
+        assert!(root.subtasks().is_empty());
+        assert_eq!(detached.subtask_count(), 1);
+    }
+    #[tokio::test]
+    async fn test_deadline() {
+        let ctx = Ctx::default();
+        assert_eq!(ctx.remaining(), None);
+        let req = ctx.with_timeout(Duration::from_millis(200));
+        let inner = req.with_timeout(Duration::from_secs(10));
+        assert!(inner.remaining().unwrap() <= Duration::from_millis(200));
+
+        let err = inner
+            .clone()
+            .call_timeout(
+                |_| async {
+                    tokio::time::sleep(Duration::from_secs(1)).await;
+                    Ok(())
+                },
+                Some(Duration::from_secs(5)),
+            )
+            .await
+            .unwrap_err();
+        assert!(err.is::<DeadlineExceeded>());
+        assert!(inner.is_deadline_exceeded());
+
+        let err = ctx
+            .clone()
+            .call_timeout(
+                |_| async {
+                    tokio::time::sleep(Duration::from_secs(1)).await;
+                    Ok(())
+                },
+                Some(Duration::from_millis(10)),
+            )
+            .await
+            .unwrap_err();
+        assert!(!err.is::<DeadlineExceeded>());
+    }
+    #[tokio::test]
+    async fn test_cancel() {
+        let ctx = Ctx::default();
+        let c = ctx.clone();
+        let call = tokio::spawn(c.call(|_| async {
+            tokio::time::sleep(Duration::from_secs(10)).await;
+            Ok(())
+        }));
+        let task = ctx.spawn(async {
+            tokio::time::sleep(Duration::from_secs(10)).await;
+        });
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        ctx.stop();
+
+        let err = call.await.unwrap().unwrap_err();
+        assert!(err.is::<Cancelled>());
+        let err = task.await.unwrap().unwrap_err();
+        assert!(err.is::<Cancelled>());
+        tokio::time::timeout(Duration::from_secs(1), ctx.wait_all_subtask_over())
+            .await
+            .unwrap();
+    }
+    #[test]
+    fn test_typed_key() {
+        const USER: TypedKey<String> = TypedKey::new("user");
+        const USER_ID: TypedKey<u64> = TypedKey::new("user");
+        let ctx = Ctx::default();
+        ctx.insert("user", 1u8);
+        assert_eq!(USER.insert(&ctx, "wd".to_string()), None);
+        USER_ID.insert(&ctx, 7);
+        assert_eq!(USER.get_cloned(&ctx).as_deref(), Some("wd"));
+        assert_eq!(USER_ID.get_cloned(&ctx), Some(7));
+        assert_eq!(USER.get_cloned(&ctx.child()).as_deref(), Some("wd"));
+        assert_eq!(USER.get(&ctx, |v| v.map(|s| s.len())), Some(2));
+
+        assert_eq!(USER.remove(&ctx).as_deref(), Some("wd"));
+        assert_eq!(USER.get_cloned(&ctx), None);
+        assert_eq!(USER_ID.remove(&ctx), Some(7));
+
+        assert_eq!(ctx.remove::<_, u16>("user"), None);
+        assert_eq!(ctx.remove::<_, u8>("user"), Some(1));
+    }
+    #[tokio::test]
+    async fn test_subtasks() {
+        let ctx = Ctx::default();
+        tokio::spawn(ctx.clone().call_named("load_user", |_| async {
+            tokio::time::sleep(Duration::from_secs(10)).await;
+            Ok(())
+        }));
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        let _task = ctx.spawn_named(format!("sync_{}", 1), async {
+            tokio::time::sleep(Duration::from_secs(10)).await;
+        });
+        tokio::spawn(ctx.clone().call(|_| async { Ok(()) }));
+        tokio::time::sleep(Duration::from_millis(50)).await;
+
+        let list = ctx.subtasks();
+        assert_eq!(list.len(), 2);
+        assert_eq!(list[0].name, "load_user");
+        assert_eq!(list[1].name, "sync_1");
+        assert!(list[0].elapsed > list[1].elapsed);
+        assert_eq!(list[0].status, SubtaskStatus::Running);
+
+        ctx.stop();
+        tokio::time::timeout(Duration::from_secs(1), ctx.wait_all_subtask_over())
+            .await
+            .unwrap();
+        assert!(ctx.subtasks().is_empty());
+    }
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_subtask_status() {
+        let ctx = Ctx::default();
+        // blocks the worker, so the timeout can not cancel it in time
+        tokio::spawn(ctx.clone().call_named_timeout(
+            "blocking",
+            |_| async {
+                std::thread::sleep(Duration::from_millis(300));
+                Ok(())
+            },
+            Some(Duration::from_millis(20)),
+        ));
+        tokio::spawn(ctx.clone().call_named("sleep", |_| async {
+            tokio::time::sleep(Duration::from_secs(10)).await;
+            Ok(())
+        }));
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        let list = ctx.subtasks();
+        assert_eq!(list.len(), 2);
+        let status = |name: &str| list.iter().find(|t| t.name == name).unwrap().status;
+        assert_eq!(status("blocking"), SubtaskStatus::Overdue);
+        assert_eq!(status("sleep"), SubtaskStatus::Running);
+
+        ctx.stop();
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        let list = ctx.subtasks();
+        assert_eq!(list.len(), 1);
+        assert_eq!(list[0].name, "blocking");
+        assert_eq!(list[0].status, SubtaskStatus::Stopping);
+        tokio::time::timeout(Duration::from_secs(1), ctx.wait_all_subtask_over())
+            .await
+            .unwrap();
+    }
 }
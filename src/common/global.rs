@@ -1,70 +1,214 @@
 use std::any::{Any, TypeId};
 use std::collections::HashMap;
-use std::sync::{Mutex, Once};
-
-static mut VARS: Option<HashMap<TypeId, Box<dyn Any>>> = None;
-static mut VARS_LOCK: Mutex<()> = Mutex::new(());
-static START: Once = Once::new();
-
-pub fn get() -> &'static mut HashMap<TypeId, Box<dyn Any>> {
-    START.call_once(|| unsafe {
-        VARS = Some(HashMap::new());
-    });
-    unsafe {
-        if let Some(ref mut s) = VARS {
-            return s;
-        } else {
-            panic!("wd_tool VARS not init!!!");
-        }
-    }
+use std::sync::{Arc, OnceLock, RwLock};
+
+type Key = (TypeId, Option<String>);
+type Value = Arc<dyn Any + Send + Sync>;
+
+static VARS: OnceLock<RwLock<HashMap<Key, Value>>> = OnceLock::new();
+
+fn vars() -> &'static RwLock<HashMap<Key, Value>> {
+    VARS.get_or_init(|| RwLock::new(HashMap::new()))
+}
+
+fn key<T: Any>(name: Option<&str>) -> Key {
+    (TypeId::of::<T>(), name.map(|s| s.to_string()))
+}
+
+fn downcast<T: Any + Send + Sync>(val: Value) -> Option<Arc<T>> {
+    val.downcast::<T>().ok()
+}
+
+fn insert<T: Any + Send + Sync>(name: Option<&str>, t: T) -> Option<Arc<T>> {
+    let old = vars().write().unwrap().insert(key::<T>(name), Arc::new(t));
+    old.and_then(downcast)
 }
 
-pub fn unsafe_init<T: Any>(t: T) {
-    let id = t.type_id();
-    get().insert(id, Box::new(t));
+fn fetch<T: Any + Send + Sync>(name: Option<&str>) -> Option<Arc<T>> {
+    let val = vars().read().unwrap().get(&key::<T>(name)).cloned();
+    val.and_then(downcast)
 }
 
-pub fn init<T: Any>(t: T) {
-    unsafe {
-        let _lock = VARS_LOCK.lock().unwrap();
-        unsafe_init(t);
+// insert only if the key is free, return whether it was inserted
+fn insert_new<T: Any + Send + Sync>(name: Option<&str>, t: T) -> bool {
+    let mut vars = vars().write().unwrap();
+    let key = key::<T>(name);
+    if vars.contains_key(&key) {
+        return false;
     }
+    vars.insert(key, Arc::new(t));
+    true
+}
+
+fn take<T: Any + Send + Sync>(name: Option<&str>) -> Option<Arc<T>> {
+    let old = vars().write().unwrap().remove(&key::<T>(name));
+    old.and_then(downcast)
+}
+
+/// 注册全局变量，同类型已存在时不覆盖并返回false，覆盖请用replace
+pub fn init<T: Any + Send + Sync>(t: T) -> bool {
+    insert_new(None, t)
 }
 
-pub fn unsafe_fetch<T: Any, Out>(handle: impl FnOnce(Option<&mut T>) -> Out) -> Out {
-    let id = TypeId::of::<T>();
-    let t = if let Some(s) = get().get_mut(&id) {
-        s
-    } else {
-        return handle(None);
-    };
-    let t = t.downcast_mut::<T>();
+pub fn init_named<T: Any + Send + Sync>(name: &str, t: T) -> bool {
+    insert_new(Some(name), t)
+}
+
+#[deprecated(note = "use replace instead")]
+pub fn unsafe_init<T: Any + Send + Sync>(t: T) {
+    insert(None, t);
+}
+
+/// handle gets None if the value is not init or an Arc from get is still alive,
+/// the registry is locked during handle, so it must not call into global
+#[deprecated(note = "use get/try_get with interior mutability instead")]
+pub fn unsafe_fetch<T: Any + Send + Sync, Out>(handle: impl FnOnce(Option<&mut T>) -> Out) -> Out {
+    let mut vars = vars().write().unwrap();
+    let t = vars
+        .get_mut(&key::<T>(None))
+        .and_then(Arc::get_mut)
+        .and_then(|v| v.downcast_mut::<T>());
     handle(t)
 }
 
+pub fn get<T: Any + Send + Sync>() -> Arc<T> {
+    match try_get::<T>() {
+        Some(s) => s,
+        None => panic!("wd_tools global[{}] not init", std::any::type_name::<T>()),
+    }
+}
+
+pub fn get_named<T: Any + Send + Sync>(name: &str) -> Arc<T> {
+    match try_get_named::<T>(name) {
+        Some(s) => s,
+        None => panic!(
+            "wd_tools global[{}:{}] not init",
+            std::any::type_name::<T>(),
+            name
+        ),
+    }
+}
+
+pub fn try_get<T: Any + Send + Sync>() -> Option<Arc<T>> {
+    fetch(None)
+}
+
+pub fn try_get_named<T: Any + Send + Sync>(name: &str) -> Option<Arc<T>> {
+    fetch(Some(name))
+}
+
+pub fn replace<T: Any + Send + Sync>(t: T) -> Option<Arc<T>> {
+    insert(None, t)
+}
+
+pub fn replace_named<T: Any + Send + Sync>(name: &str, t: T) -> Option<Arc<T>> {
+    insert(Some(name), t)
+}
+
+pub fn remove<T: Any + Send + Sync>() -> Option<Arc<T>> {
+    take(None)
+}
+
+pub fn remove_named<T: Any + Send + Sync>(name: &str) -> Option<Arc<T>> {
+    take(Some(name))
+}
+
+/// 临时替换全局变量，guard销毁时恢复旧值，用于测试
+/// 同一个key上的guard必须按创建的相反顺序销毁（LIFO），否则恢复的值是错的
+pub struct OverrideGuard {
+    key: Key,
+    old: Option<Value>,
+}
+
+impl Drop for OverrideGuard {
+    fn drop(&mut self) {
+        let mut vars = vars().write().unwrap();
+        match self.old.take() {
+            Some(old) => vars.insert(self.key.clone(), old),
+            None => vars.remove(&self.key),
+        };
+    }
+}
+
+pub fn override_scoped<T: Any + Send + Sync>(t: T) -> OverrideGuard {
+    override_scoped_inner(None, t)
+}
+
+pub fn override_scoped_named<T: Any + Send + Sync>(name: &str, t: T) -> OverrideGuard {
+    override_scoped_inner(Some(name), t)
+}
+
+fn override_scoped_inner<T: Any + Send + Sync>(name: Option<&str>, t: T) -> OverrideGuard {
+    let key = key::<T>(name);
+    let old = vars().write().unwrap().insert(key.clone(), Arc::new(t));
+    OverrideGuard { key, old }
+}
+
 #[cfg(test)]
 mod test {
+    use std::sync::atomic::{AtomicU32, Ordering};
 
     #[test]
     fn test_vars() {
-        super::init(1u32);
-        let i = super::unsafe_fetch(|f| {
-            if let Some(s) = f {
-                *s += 1;
-                *s
-            } else {
-                0u32
-            }
-        });
+        assert!(super::init(AtomicU32::new(1)));
+        // init does not overwrite the value
+        assert!(!super::init(AtomicU32::new(10)));
+        let i = super::get::<AtomicU32>().fetch_add(1, Ordering::SeqCst) + 1;
         assert_eq!(2, i);
-        let i = super::unsafe_fetch(|f| {
-            if let Some(s) = f {
-                *s += 1;
-                *s
-            } else {
-                0u32
-            }
-        });
+        let i = super::get::<AtomicU32>().fetch_add(1, Ordering::SeqCst) + 1;
         assert_eq!(3, i);
     }
+
+    #[test]
+    #[allow(deprecated)]
+    fn test_unsafe_vars() {
+        struct Counter(u32);
+        super::unsafe_init(Counter(1));
+        let add = || {
+            super::unsafe_fetch(|c: Option<&mut Counter>| {
+                c.map(|c| {
+                    c.0 += 1;
+                    c.0
+                })
+            })
+        };
+        assert_eq!(add(), Some(2));
+        assert_eq!(add(), Some(3));
+        let shared = super::get::<Counter>();
+        assert_eq!(add(), None);
+        drop(shared);
+        assert_eq!(add(), Some(4));
+    }
+
+    #[test]
+    fn test_named_vars() {
+        super::init_named("a", String::from("hello"));
+        super::init_named("b", String::from("world"));
+        assert_eq!(super::get_named::<String>("a").as_str(), "hello");
+        assert_eq!(super::get_named::<String>("b").as_str(), "world");
+        assert!(super::try_get::<String>().is_none());
+
+        let old = super::replace_named("a", String::from("hi"));
+        assert_eq!(old.unwrap().as_str(), "hello");
+        assert_eq!(super::get_named::<String>("a").as_str(), "hi");
+
+        let old = super::remove_named::<String>("b");
+        assert_eq!(old.unwrap().as_str(), "world");
+        assert!(super::try_get_named::<String>("b").is_none());
+    }
+
+    #[test]
+    fn test_override_scoped() {
+        super::init(1u64);
+        {
+            let _guard = super::override_scoped(2u64);
+            assert_eq!(*super::get::<u64>(), 2);
+        }
+        assert_eq!(*super::get::<u64>(), 1);
+        {
+            let _guard = super::override_scoped_named("tmp", 3u64);
+            assert_eq!(*super::get_named::<u64>("tmp"), 3);
+        }
+        assert!(super::try_get_named::<u64>("tmp").is_none());
+    }
 }
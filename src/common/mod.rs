@@ -28,7 +28,19 @@ pub use hex::EncodeHex;
 mod ctx;
 
 #[cfg(feature = "ctx")]
-pub use ctx::Ctx;
+pub use ctx::{Cancelled, Ctx, DeadlineExceeded, SubtaskInfo, SubtaskStatus, TypedKey};
+
+#[cfg(feature = "ctx")]
+mod trace;
+
+#[cfg(feature = "ctx")]
+pub use trace::{TraceContext, BAGGAGE_HEADER, TRACEPARENT_HEADER};
+
+#[cfg(feature = "shutdown")]
+mod shutdown;
+
+#[cfg(feature = "shutdown")]
+pub use shutdown::{Shutdown, ShutdownReport};
 
 #[cfg(feature = "regex_simple")]
 mod regex;
@@ -38,3 +50,9 @@ pub use regex::*;
 
 #[cfg(feature = "global")]
 pub mod global;
+
+#[cfg(feature = "container")]
+mod container;
+
+#[cfg(feature = "container")]
+pub use container::{Container, Resolver};
@@ -0,0 +1,224 @@
+use crate::{Ctx, SubtaskInfo};
+use std::future::Future;
+use std::pin::Pin;
+use std::time::{Duration, Instant};
+
+type HookFuture = Pin<Box<dyn Future<Output = anyhow::Result<()>> + Send>>;
+type Hook = Box<dyn FnOnce(Ctx) -> HookFuture + Send>;
+
+/// 优雅退出
+/// 收到SIGINT/SIGTERM后停止根ctx，按order顺序执行hook，在grace period内等待所有子任务结束
+/// hook拿到的ctx与根ctx分离，不会被stop取消，deadline为grace period结束时间
+pub struct Shutdown {
+    ctx: Ctx,
+    grace: Duration,
+    exit_code: Option<i32>,
+    hooks: Vec<(i32, String, Hook)>,
+}
+
+#[derive(Debug)]
+pub struct ShutdownReport {
+    pub signal: Option<&'static str>,
+    pub hook_errors: Vec<(String, anyhow::Error)>,
+    pub pending_subtask: isize,
+    // named subtasks that were still running after the grace period
+    pub pending: Vec<SubtaskInfo>,
+    pub elapsed: Duration,
+}
+
+impl ShutdownReport {
+    pub fn is_graceful(&self) -> bool {
+        self.pending_subtask <= 0 && self.hook_errors.is_empty()
+    }
+}
+
+impl Shutdown {
+    pub fn new(ctx: Ctx) -> Self {
+        Self {
+            ctx,
+            grace: Duration::from_secs(30),
+            exit_code: None,
+            hooks: vec![],
+        }
+    }
+    pub fn grace_period(mut self, grace: Duration) -> Self {
+        self.grace = grace;
+        self
+    }
+    // exit the process with code if subtasks are still running after the grace period
+    pub fn exit_on_timeout(mut self, code: i32) -> Self {
+        self.exit_code = Some(code);
+        self
+    }
+    // hooks are executed in ascending order, the same order keeps the registration order
+    pub fn hook<N, F, Fut>(mut self, name: N, order: i32, hook: F) -> Self
+    where
+        N: Into<String>,
+        F: FnOnce(Ctx) -> Fut + Send + 'static,
+        Fut: Future<Output = anyhow::Result<()>> + Send + 'static,
+    {
+        let hook: Hook = Box::new(move |ctx| Box::pin(hook(ctx)));
+        self.hooks.push((order, name.into(), hook));
+        self
+    }
+    pub async fn wait_signal(self) -> ShutdownReport {
+        let signal = Self::signal().await;
+        self.run(Some(signal)).await
+    }
+    pub async fn shutdown(self) -> ShutdownReport {
+        self.run(None).await
+    }
+    #[cfg(unix)]
+    async fn signal() -> &'static str {
+        use tokio::signal::unix::{signal, SignalKind};
+        let mut term = match signal(SignalKind::terminate()) {
+            Ok(o) => o,
+            Err(_) => {
+                let _ = tokio::signal::ctrl_c().await;
+                return "SIGINT";
+            }
+        };
+        tokio::select! {
+            _ = tokio::signal::ctrl_c() => "SIGINT",
+            _ = term.recv() => "SIGTERM",
+        }
+    }
+    #[cfg(not(unix))]
+    async fn signal() -> &'static str {
+        let _ = tokio::signal::ctrl_c().await;
+        "SIGINT"
+    }
+    async fn run(mut self, signal: Option<&'static str>) -> ShutdownReport {
+        let start = Instant::now();
+        let deadline = start + self.grace;
+        self.ctx.stop();
+
+        self.hooks.sort_by_key(|(order, _, _)| *order);
+        let mut hook_errors = vec![];
+        for (_, name, hook) in self.hooks {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            let ctx = self.ctx.detach().with_deadline(deadline);
+            match tokio::time::timeout(remaining, hook(ctx)).await {
+                Ok(Ok(_)) => {}
+                Ok(Err(e)) => hook_errors.push((name, e)),
+                Err(e) => hook_errors.push((name, anyhow::Error::new(e))),
+            }
+        }
+
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        let _ = tokio::time::timeout(remaining, self.ctx.wait_all_subtask_over()).await;
+        let report = ShutdownReport {
+            signal,
+            hook_errors,
+            pending_subtask: self.ctx.subtask_count(),
+            pending: self.ctx.subtasks(),
+            elapsed: start.elapsed(),
+        };
+        if let Some(code) = self.exit_code {
+            if report.pending_subtask > 0 {
+                std::process::exit(code);
+            }
+        }
+        report
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Shutdown;
+    use crate::Ctx;
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_shutdown() {
+        let ctx = Ctx::default();
+        let order = Arc::new(Mutex::new(vec![]));
+        let (o1, o2, o3) = (order.clone(), order.clone(), order.clone());
+        // a subtask that does not care about stop
+        ctx.add_task(1);
+        let c = ctx.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            c.done_task();
+        });
+        let report = Shutdown::new(ctx.clone())
+            .grace_period(Duration::from_secs(1))
+            .hook("db", 2, move |_| async move {
+                o1.lock().unwrap().push("db");
+                Ok(())
+            })
+            .hook("http", 1, move |ctx| async move {
+                assert!(!ctx.is_stop());
+                assert!(ctx.remaining().unwrap() <= Duration::from_secs(1));
+                ctx.call(|_| async move {
+                    o2.lock().unwrap().push("http");
+                    Ok(())
+                })
+                .await
+            })
+            .hook("mq", 2, move |_| async move {
+                o3.lock().unwrap().push("mq");
+                Err(anyhow::anyhow!("mq close failed"))
+            })
+            .shutdown()
+            .await;
+        assert_eq!(*order.lock().unwrap(), vec!["http", "db", "mq"]);
+        assert_eq!(report.hook_errors.len(), 1);
+        assert_eq!(report.pending_subtask, 0);
+        assert!(ctx.is_stop());
+
+        let ctx = Ctx::default();
+        // named subtask is cancelled by stop, the manual one is pending
+        tokio::spawn(ctx.clone().exec_named_future(
+            "ignore_stop",
+            async {
+                tokio::time::sleep(Duration::from_secs(10)).await;
+                Ok(())
+            },
+            None,
+        ));
+        ctx.add_task(1);
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        let report = Shutdown::new(ctx)
+            .grace_period(Duration::from_millis(100))
+            .shutdown()
+            .await;
+        assert!(!report.is_graceful());
+        assert_eq!(report.pending_subtask, 1);
+        assert!(report.pending.is_empty());
+    }
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_shutdown_child_subtask() {
+        let ctx = Ctx::default();
+        // a subtask of a request ctx that finishes its work after stop
+        let req = ctx.with_timeout(Duration::from_secs(10));
+        let guard = req.clone();
+        guard.add_task(1);
+        tokio::spawn(async move {
+            guard.wait_stop_status().await;
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            guard.done_task();
+        });
+        let report = Shutdown::new(ctx.clone())
+            .grace_period(Duration::from_secs(1))
+            .shutdown()
+            .await;
+        assert!(report.is_graceful());
+        assert!(report.elapsed >= Duration::from_millis(50));
+
+        // the named subtask of a child ctx blocks a worker, it is reported as pending
+        let ctx = Ctx::default();
+        let _task = ctx.child().spawn_named("child_task", async {
+            std::thread::sleep(Duration::from_millis(300));
+        });
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        let report = Shutdown::new(ctx)
+            .grace_period(Duration::from_millis(50))
+            .shutdown()
+            .await;
+        assert_eq!(report.pending_subtask, 1);
+        assert_eq!(report.pending.len(), 1);
+        assert_eq!(report.pending[0].name, "child_task");
+    }
+}
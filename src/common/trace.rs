@@ -0,0 +1,325 @@
+use crate::common::ctx::{Ctx, TypedKey};
+use std::collections::hash_map::RandomState;
+use std::collections::HashMap;
+use std::hash::{BuildHasher, Hasher};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+pub const TRACEPARENT_HEADER: &str = "traceparent";
+pub const BAGGAGE_HEADER: &str = "baggage";
+
+const TRACE_KEY: TypedKey<TraceContext> = TypedKey::new("wd_tools.trace");
+
+/// W3C trace context: https://www.w3.org/TR/trace-context/
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TraceContext {
+    pub trace_id: u128,
+    pub span_id: u64,
+    pub parent_span_id: Option<u64>,
+    pub sampled: bool,
+    pub baggage: Vec<(String, String)>,
+}
+
+fn random_u64() -> u64 {
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+    let mut hasher = RandomState::new().build_hasher();
+    hasher.write_u64(COUNTER.fetch_add(1, Ordering::Relaxed));
+    if let Ok(d) = SystemTime::now().duration_since(UNIX_EPOCH) {
+        hasher.write_u128(d.as_nanos());
+    }
+    match hasher.finish() {
+        0 => 1,
+        n => n,
+    }
+}
+
+// percent-encode the chars that are not allowed in baggage values
+fn encode_baggage(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for b in s.bytes() {
+        match b {
+            b'!' | b'#'..=b'+' | b'-'..=b':' | b'<' | b'>'..=b'[' | b']'..=b'~' if b != b'%' => {
+                out.push(b as char)
+            }
+            _ => out.push_str(&format!("%{:02X}", b)),
+        }
+    }
+    out
+}
+
+fn decode_baggage(s: &str) -> String {
+    let bytes = s.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' && i + 2 < bytes.len() {
+            let hex = std::str::from_utf8(&bytes[i + 1..i + 3]).ok();
+            if let Some(b) = hex.and_then(|h| u8::from_str_radix(h, 16).ok()) {
+                out.push(b);
+                i += 3;
+                continue;
+            }
+        }
+        out.push(bytes[i]);
+        i += 1;
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+// exactly len chars of [0-9a-f]
+fn is_lower_hex(s: &str, len: usize) -> bool {
+    s.len() == len && s.bytes().all(|b| matches!(b, b'0'..=b'9' | b'a'..=b'f'))
+}
+
+impl Default for TraceContext {
+    fn default() -> Self {
+        Self::new_root()
+    }
+}
+
+impl TraceContext {
+    pub fn new_root() -> Self {
+        let trace_id = ((random_u64() as u128) << 64) | random_u64() as u128;
+        Self {
+            trace_id,
+            span_id: random_u64(),
+            parent_span_id: None,
+            sampled: true,
+            baggage: vec![],
+        }
+    }
+    pub fn child_span(&self) -> Self {
+        Self {
+            trace_id: self.trace_id,
+            span_id: random_u64(),
+            parent_span_id: Some(self.span_id),
+            sampled: self.sampled,
+            baggage: self.baggage.clone(),
+        }
+    }
+    pub fn trace_id_hex(&self) -> String {
+        format!("{:032x}", self.trace_id)
+    }
+    pub fn span_id_hex(&self) -> String {
+        format!("{:016x}", self.span_id)
+    }
+    pub fn get_baggage(&self, key: &str) -> Option<&str> {
+        self.baggage
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v.as_str())
+    }
+    pub fn set_baggage<K: Into<String>, V: Into<String>>(&mut self, key: K, value: V) {
+        let key = key.into();
+        let value = value.into();
+        match self.baggage.iter_mut().find(|(k, _)| *k == key) {
+            Some(kv) => kv.1 = value,
+            None => self.baggage.push((key, value)),
+        }
+    }
+    pub fn traceparent(&self) -> String {
+        format!(
+            "00-{}-{}-{:02x}",
+            self.trace_id_hex(),
+            self.span_id_hex(),
+            self.sampled as u8
+        )
+    }
+    pub fn parse_traceparent(s: &str) -> Option<Self> {
+        let mut parts = s.trim().split('-');
+        let version = parts.next()?;
+        let trace_id = parts.next()?;
+        let span_id = parts.next()?;
+        let flags = parts.next()?;
+        if !is_lower_hex(version, 2)
+            || version == "ff"
+            || !is_lower_hex(trace_id, 32)
+            || !is_lower_hex(span_id, 16)
+            || !is_lower_hex(flags, 2)
+        {
+            return None;
+        }
+        // version 00 has exactly four fields, later versions may append more
+        if version == "00" && parts.next().is_some() {
+            return None;
+        }
+        let trace_id = u128::from_str_radix(trace_id, 16).ok()?;
+        let span_id = u64::from_str_radix(span_id, 16).ok()?;
+        let flags = u8::from_str_radix(flags, 16).ok()?;
+        if trace_id == 0 || span_id == 0 {
+            return None;
+        }
+        Some(Self {
+            trace_id,
+            span_id,
+            parent_span_id: None,
+            sampled: flags & 1 == 1,
+            baggage: vec![],
+        })
+    }
+    pub fn baggage_header(&self) -> Option<String> {
+        if self.baggage.is_empty() {
+            return None;
+        }
+        let list = self
+            .baggage
+            .iter()
+            .map(|(k, v)| format!("{}={}", k, encode_baggage(v)))
+            .collect::<Vec<_>>();
+        Some(list.join(","))
+    }
+    pub fn parse_baggage(s: &str) -> Vec<(String, String)> {
+        s.split(',')
+            .filter_map(|item| {
+                // properties after ';' are ignored
+                let kv = item.split(';').next()?;
+                let (k, v) = kv.split_once('=')?;
+                let k = k.trim();
+                if k.is_empty() {
+                    return None;
+                }
+                Some((k.to_string(), decode_baggage(v.trim())))
+            })
+            .collect()
+    }
+    pub fn to_headers(&self) -> HashMap<String, String> {
+        let mut headers = HashMap::new();
+        headers.insert(TRACEPARENT_HEADER.to_string(), self.traceparent());
+        if let Some(baggage) = self.baggage_header() {
+            headers.insert(BAGGAGE_HEADER.to_string(), baggage);
+        }
+        headers
+    }
+    // return None if there is no valid traceparent header
+    pub fn from_headers<K, V, I>(headers: I) -> Option<Self>
+    where
+        K: AsRef<str>,
+        V: AsRef<str>,
+        I: IntoIterator<Item = (K, V)>,
+    {
+        let mut traceparent = None;
+        let mut baggage = vec![];
+        for (k, v) in headers {
+            let k = k.as_ref();
+            if k.eq_ignore_ascii_case(TRACEPARENT_HEADER) {
+                traceparent = Self::parse_traceparent(v.as_ref());
+            } else if k.eq_ignore_ascii_case(BAGGAGE_HEADER) {
+                baggage.extend(Self::parse_baggage(v.as_ref()));
+            }
+        }
+        let mut tc = traceparent?;
+        tc.baggage = baggage;
+        Some(tc)
+    }
+}
+
+impl Ctx {
+    #[allow(dead_code)]
+    pub fn trace(&self) -> Option<TraceContext> {
+        TRACE_KEY.get_cloned(self)
+    }
+    #[allow(dead_code)]
+    pub fn set_trace(&self, trace: TraceContext) {
+        TRACE_KEY.insert(self, trace);
+    }
+    #[allow(dead_code)]
+    pub fn trace_id(&self) -> Option<String> {
+        TRACE_KEY.get(self, |t| t.map(|t| t.trace_id_hex()))
+    }
+    // child ctx with a new span of the current trace, a new trace is started if there is none
+    #[allow(dead_code)]
+    pub fn child_span(&self) -> Ctx {
+        let trace = match self.trace() {
+            Some(t) => t.child_span(),
+            None => TraceContext::new_root(),
+        };
+        let child = self.child();
+        child.set_trace(trace);
+        child
+    }
+    // build a ctx from incoming headers, a new trace is started if there is no traceparent
+    #[allow(dead_code)]
+    pub fn from_headers<K, V, I>(headers: I) -> Ctx
+    where
+        K: AsRef<str>,
+        V: AsRef<str>,
+        I: IntoIterator<Item = (K, V)>,
+    {
+        let ctx = Ctx::default();
+        let trace = TraceContext::from_headers(headers).unwrap_or_default();
+        ctx.set_trace(trace);
+        ctx
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::TraceContext;
+    use crate::Ctx;
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_traceparent() {
+        let s = "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01";
+        let tc = TraceContext::parse_traceparent(s).unwrap();
+        assert_eq!(tc.trace_id_hex(), "4bf92f3577b34da6a3ce929d0e0e4736");
+        assert_eq!(tc.span_id, 0x00f067aa0ba902b7);
+        assert!(tc.sampled);
+        assert_eq!(tc.traceparent(), s);
+        assert!(TraceContext::parse_traceparent("00-0000-00f067aa0ba902b7-01").is_none());
+        assert!(TraceContext::parse_traceparent(
+            "00-00000000000000000000000000000000-00f067aa0ba902b7-01"
+        )
+        .is_none());
+        for bad in [
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-0000000000000000-01",
+            "00-4BF92F3577B34DA6A3CE929D0E0E4736-00f067aa0ba902b7-01",
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-00F067AA0BA902B7-01",
+            "00-+bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01",
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-+0f067aa0ba902b7-01",
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-1",
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-001",
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-+1",
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-0A",
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01-xx",
+            "0-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01",
+            "ff-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01",
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7",
+        ] {
+            assert!(TraceContext::parse_traceparent(bad).is_none(), "{}", bad);
+        }
+        let future = "01-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01-xx";
+        assert!(TraceContext::parse_traceparent(future).is_some());
+    }
+
+    #[test]
+    fn test_trace_ctx() {
+        let mut headers = HashMap::new();
+        headers.insert(
+            "Traceparent",
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-00",
+        );
+        headers.insert("baggage", "user=wd%20d,tenant=1;prop=x");
+        let ctx = Ctx::from_headers(headers);
+        let tc = ctx.trace().unwrap();
+        assert!(!tc.sampled);
+        assert_eq!(tc.get_baggage("user"), Some("wd d"));
+        assert_eq!(tc.get_baggage("tenant"), Some("1"));
+
+        let child = ctx.child_span();
+        let span = child.trace().unwrap();
+        assert_eq!(span.trace_id, tc.trace_id);
+        assert_eq!(span.parent_span_id, Some(tc.span_id));
+        assert_ne!(span.span_id, tc.span_id);
+        assert_eq!(ctx.trace().unwrap(), tc);
+
+        let headers = span.to_headers();
+        assert_eq!(headers["baggage"], "user=wd%20d,tenant=1");
+        let back = TraceContext::from_headers(&headers).unwrap();
+        assert_eq!(back.span_id, span.span_id);
+        assert_eq!(back.baggage, span.baggage);
+
+        let ctx = Ctx::from_headers(Vec::<(String, String)>::new());
+        assert_eq!(ctx.trace_id().unwrap().len(), 32);
+    }
+}
@@ -44,6 +44,14 @@ pub struct Http {
     response_hook: Option<Arc<dyn ResponseHook>>,
 }
 
+// build a ctx from the headers of an incoming request
+pub fn ctx_from_header_map(headers: &HeaderMap) -> Ctx {
+    let iter = headers
+        .iter()
+        .filter_map(|(k, v)| Some((k.as_str(), v.to_str().ok()?)));
+    Ctx::from_headers(iter)
+}
+
 impl Clone for Http {
     fn clone(&self) -> Self {
         Self {
@@ -131,7 +139,21 @@ impl Http {
     pub async fn send_no_body<T: Any>(&self) -> anyhow::Result<T> {
         self.clone().into_send().await
     }
+    // inject the trace of ctx as traceparent/baggage headers, headers set by user take precedence
+    fn inject_trace(ctx: &Ctx, mut builder: RequestBuilder) -> RequestBuilder {
+        if let Some(trace) = ctx.trace() {
+            for (k, v) in trace.to_headers() {
+                builder = builder.header(k, v);
+            }
+        }
+        builder
+    }
+    // the request is limited by the deadline of hook_ctx
     pub async fn into_send<T: Any>(self) -> anyhow::Result<T> {
+        let ctx = self.hook_ctx.clone();
+        ctx.timeout_future(self.send_inner(), None).await
+    }
+    async fn send_inner<T: Any>(self) -> anyhow::Result<T> {
         let builder = Client::builder();
         let client = match self.client_build_hook {
             None => builder.build()?,
@@ -139,6 +161,7 @@ impl Http {
         };
 
         let mut builder = client.request(self.method, self.url);
+        builder = Self::inject_trace(&self.hook_ctx, builder);
         if let Some(headers) = self.header {
             builder = builder.headers(HeaderMap::try_from(&headers).unwrap());
         }
@@ -170,6 +193,7 @@ impl Http {
 mod test {
     use super::{Method, Response};
     use crate::http::{Http, StatusCode};
+    use crate::{Ctx, DeadlineExceeded, TraceContext};
     use std::any::Any;
     use std::time::Duration;
 
@@ -204,4 +228,33 @@ mod test {
         assert_eq!(code.as_u16(), 200u16);
         println!("{}-->{}", code, body)
     }
+
+    #[tokio::test]
+    async fn test_deadline() {
+        let mut http = Http::new(Method::GET, "https://crates.io/api/v1/crates/wd_tools").unwrap();
+        http.hook_ctx = Ctx::default().with_timeout(Duration::from_millis(1));
+        tokio::time::sleep(Duration::from_millis(2)).await;
+        let err = http.send_no_body::<Response>().await.unwrap_err();
+        assert!(err.is::<DeadlineExceeded>());
+    }
+
+    #[test]
+    fn test_inject_trace() {
+        let ctx = Ctx::default().child_span();
+        let mut trace = ctx.trace().unwrap();
+        trace.set_baggage("user", "wd");
+        ctx.set_trace(trace.clone());
+
+        let builder = super::Client::new().get("http://localhost");
+        let req = Http::inject_trace(&ctx, builder).build().unwrap();
+        let headers = req.headers();
+        assert_eq!(headers["traceparent"], trace.traceparent().as_str());
+        assert_eq!(headers["baggage"], "user=wd");
+
+        let incoming = super::ctx_from_header_map(headers);
+        let back: TraceContext = incoming.trace().unwrap();
+        assert_eq!(back.trace_id, trace.trace_id);
+        assert_eq!(back.span_id, trace.span_id);
+        assert_eq!(back.get_baggage("user"), Some("wd"));
+    }
 }
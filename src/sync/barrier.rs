@@ -0,0 +1,143 @@
+use std::sync::{Arc, Condvar, Mutex};
+use tokio::sync::Notify;
+
+/// 屏障
+/// n个任务全部到达后一起放行，可重复使用，每一轮中最后到达的任务为leader
+pub struct Barrier {
+    n: usize,
+    state: Arc<Mutex<BarrierState>>,
+    notify: Arc<Notify>,
+    cond: Arc<Condvar>,
+}
+
+#[derive(Debug, Default)]
+struct BarrierState {
+    count: usize,
+    generation: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BarrierWaitResult(bool);
+
+impl BarrierWaitResult {
+    pub fn is_leader(&self) -> bool {
+        self.0
+    }
+}
+
+impl Clone for Barrier {
+    fn clone(&self) -> Self {
+        Self {
+            n: self.n,
+            state: self.state.clone(),
+            notify: self.notify.clone(),
+            cond: self.cond.clone(),
+        }
+    }
+}
+
+impl Barrier {
+    pub fn new(n: usize) -> Self {
+        Self {
+            n: n.max(1),
+            state: Arc::new(Mutex::new(BarrierState::default())),
+            notify: Arc::new(Notify::new()),
+            cond: Arc::new(Condvar::new()),
+        }
+    }
+    pub fn parties(&self) -> usize {
+        self.n
+    }
+    // return Ok(result) if this call completes the round, otherwise Err(generation) to wait on
+    fn arrive(&self) -> Result<BarrierWaitResult, usize> {
+        let mut state = self.state.lock().unwrap();
+        state.count += 1;
+        if state.count < self.n {
+            return Err(state.generation);
+        }
+        state.count = 0;
+        state.generation = state.generation.wrapping_add(1);
+        drop(state);
+        self.notify.notify_waiters();
+        self.cond.notify_all();
+        Ok(BarrierWaitResult(true))
+    }
+    fn generation(&self) -> usize {
+        self.state.lock().unwrap().generation
+    }
+    pub async fn wait(&self) -> BarrierWaitResult {
+        // register before arriving, so the release of this round can't be missed
+        let mut notified = Box::pin(self.notify.notified());
+        let generation = match self.arrive() {
+            Ok(o) => return o,
+            Err(g) => g,
+        };
+        loop {
+            if self.generation() != generation {
+                return BarrierWaitResult(false);
+            }
+            notified.as_mut().await;
+            notified.set(self.notify.notified());
+        }
+    }
+    pub fn blocking_wait(&self) -> BarrierWaitResult {
+        let generation = match self.arrive() {
+            Ok(o) => return o,
+            Err(g) => g,
+        };
+        let state = self.state.lock().unwrap();
+        let _state = self
+            .cond
+            .wait_while(state, |s| s.generation == generation)
+            .unwrap();
+        BarrierWaitResult(false)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::sync::{Barrier, WaitGroup};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_barrier() {
+        let barrier = Barrier::new(5);
+        let leaders = Arc::new(AtomicUsize::new(0));
+        let wg = WaitGroup::default();
+        for _ in 0..10 {
+            let barrier = barrier.clone();
+            let leaders = leaders.clone();
+            wg.defer(move || async move {
+                if barrier.wait().await.is_leader() {
+                    leaders.fetch_add(1, Ordering::Relaxed);
+                }
+            });
+        }
+        wg.wait().await;
+        assert_eq!(leaders.load(Ordering::Relaxed), 2);
+    }
+
+    #[test]
+    fn test_barrier_blocking() {
+        let barrier = Barrier::new(4);
+        let leaders = Arc::new(AtomicUsize::new(0));
+        let handles = (0..4)
+            .map(|_| {
+                let barrier = barrier.clone();
+                let leaders = leaders.clone();
+                std::thread::spawn(move || {
+                    for _ in 0..3 {
+                        if barrier.blocking_wait().is_leader() {
+                            leaders.fetch_add(1, Ordering::Relaxed);
+                        }
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+        for h in handles {
+            h.join().unwrap();
+        }
+        assert_eq!(leaders.load(Ordering::Relaxed), 3);
+    }
+}
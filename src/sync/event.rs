@@ -0,0 +1,147 @@
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::Duration;
+use tokio::sync::Notify;
+
+/// 事件
+/// manual: set后所有等待者放行，直到reset
+/// auto: set后只放行一个等待者，并自动reset
+pub struct Event {
+    auto_reset: bool,
+    status: Arc<Mutex<bool>>,
+    notify: Arc<Notify>,
+    cond: Arc<Condvar>,
+}
+
+impl Clone for Event {
+    fn clone(&self) -> Self {
+        Self {
+            auto_reset: self.auto_reset,
+            status: self.status.clone(),
+            notify: self.notify.clone(),
+            cond: self.cond.clone(),
+        }
+    }
+}
+
+impl Event {
+    fn new(auto_reset: bool) -> Self {
+        Self {
+            auto_reset,
+            status: Arc::new(Mutex::new(false)),
+            notify: Arc::new(Notify::new()),
+            cond: Arc::new(Condvar::new()),
+        }
+    }
+    pub fn manual() -> Self {
+        Self::new(false)
+    }
+    pub fn auto() -> Self {
+        Self::new(true)
+    }
+    pub fn is_auto_reset(&self) -> bool {
+        self.auto_reset
+    }
+    pub fn is_set(&self) -> bool {
+        *self.status.lock().unwrap()
+    }
+    pub fn set(&self) {
+        let mut status = self.status.lock().unwrap();
+        *status = true;
+        drop(status);
+        if self.auto_reset {
+            self.notify.notify_one();
+            self.cond.notify_one();
+        } else {
+            self.notify.notify_waiters();
+            self.cond.notify_all();
+        }
+    }
+    pub fn reset(&self) {
+        *self.status.lock().unwrap() = false;
+    }
+    // consume the signal if it is set
+    fn try_take(&self, status: &mut bool) -> bool {
+        if !*status {
+            return false;
+        }
+        if self.auto_reset {
+            *status = false;
+        }
+        true
+    }
+    pub async fn wait(&self) {
+        loop {
+            let notified = self.notify.notified();
+            if self.try_take(&mut self.status.lock().unwrap()) {
+                return;
+            }
+            notified.await;
+        }
+    }
+    pub fn blocking_wait(&self) {
+        let mut status = self.status.lock().unwrap();
+        while !self.try_take(&mut status) {
+            status = self.cond.wait(status).unwrap();
+        }
+    }
+    // return false if timeout
+    pub fn blocking_wait_timeout(&self, timeout: Duration) -> bool {
+        let deadline = std::time::Instant::now() + timeout;
+        let mut status = self.status.lock().unwrap();
+        while !self.try_take(&mut status) {
+            let now = std::time::Instant::now();
+            if now >= deadline {
+                return false;
+            }
+            status = self.cond.wait_timeout(status, deadline - now).unwrap().0;
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::sync::{Event, WaitGroup};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_event_manual() {
+        let event = Event::manual();
+        let wg = WaitGroup::default();
+        for _ in 0..5 {
+            let event = event.clone();
+            wg.defer(move || async move { event.wait().await });
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        event.set();
+        wg.wait().await;
+        assert!(event.is_set());
+        event.reset();
+        assert!(!event.blocking_wait_timeout(Duration::from_millis(10)));
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_event_auto() {
+        let event = Event::auto();
+        let passed = Arc::new(AtomicUsize::new(0));
+        for _ in 0..3 {
+            let event = event.clone();
+            let passed = passed.clone();
+            tokio::spawn(async move {
+                event.wait().await;
+                passed.fetch_add(1, Ordering::Relaxed);
+            });
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        event.set();
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert_eq!(passed.load(Ordering::Relaxed), 1);
+        assert!(!event.is_set());
+        event.set();
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert_eq!(passed.load(Ordering::Relaxed), 2);
+        assert!(!event.blocking_wait_timeout(Duration::from_millis(10)));
+    }
+}
@@ -1,3 +1,75 @@
+use std::future::Future;
+use std::sync::{Arc, OnceLock, RwLock};
+
+/// 异步全局变量容器
+/// 初始化失败时返回错误，下一次访问会重新初始化；可以在测试中重置或替换
+pub struct AsyncGlobal<L> {
+    value: RwLock<Option<Arc<L>>>,
+    init_lock: OnceLock<tokio::sync::Mutex<()>>,
+}
+
+impl<L> Default for AsyncGlobal<L> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<L> AsyncGlobal<L> {
+    pub const fn new() -> Self {
+        Self {
+            value: RwLock::new(None),
+            init_lock: OnceLock::new(),
+        }
+    }
+    pub fn get(&self) -> Option<Arc<L>> {
+        self.value.read().unwrap().clone()
+    }
+    pub fn set(&self, l: L) -> Option<Arc<L>> {
+        self.value.write().unwrap().replace(Arc::new(l))
+    }
+    pub fn reset(&self) -> Option<Arc<L>> {
+        self.value.write().unwrap().take()
+    }
+    pub fn is_init(&self) -> bool {
+        self.value.read().unwrap().is_some()
+    }
+    pub async fn get_or_try_init<F, Fut>(&self, init: F) -> anyhow::Result<Arc<L>>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = anyhow::Result<L>>,
+    {
+        if let Some(s) = self.get() {
+            return Ok(s);
+        }
+        let _lock = self
+            .init_lock
+            .get_or_init(|| tokio::sync::Mutex::new(()))
+            .lock()
+            .await;
+        if let Some(s) = self.get() {
+            return Ok(s);
+        }
+        let l = Arc::new(init().await?);
+        *self.value.write().unwrap() = Some(l.clone());
+        Ok(l)
+    }
+    // run init again and replace the old value, the old value is kept if init failed
+    pub async fn try_reinit<F, Fut>(&self, init: F) -> anyhow::Result<Arc<L>>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = anyhow::Result<L>>,
+    {
+        let _lock = self
+            .init_lock
+            .get_or_init(|| tokio::sync::Mutex::new(()))
+            .lock()
+            .await;
+        let l = Arc::new(init().await?);
+        *self.value.write().unwrap() = Some(l.clone());
+        Ok(l)
+    }
+}
+
 
 #[macro_export]
 macro_rules! share {
@@ -77,6 +149,124 @@ macro_rules! global {
     };
 }
 
+#[macro_export]
+macro_rules! global_async {
+    ($type_name:ident,$init_func:block) => {
+        paste::paste! {
+            #[allow(non_snake_case,non_upper_case_globals)]
+            static [<__ $type_name _ASYNC_GLOBAL>]: $crate::sync::global::AsyncGlobal<$crate::sync::AsyncMutex<$type_name>> = $crate::sync::global::AsyncGlobal::new();
+
+            #[allow(non_snake_case)]
+            async fn [<_init_ $type_name>]() -> anyhow::Result<$crate::sync::AsyncMutex<$type_name>> {
+                let t: anyhow::Result<$type_name> = async move $init_func.await;
+                Ok($crate::sync::AsyncMutex::new(t?))
+            }
+
+            global_async!(@common $type_name, $crate::sync::AsyncMutex<$type_name>);
+
+            impl $type_name {
+                #[allow(dead_code)]
+                pub async fn async_ref<T, Out>(handle: T) -> anyhow::Result<Out>
+                where
+                    T: FnOnce(&mut $type_name) -> Out,
+                {
+                    let this = Self::global().await?;
+                    let mut target = this.lock().await;
+                    Ok(handle(std::ops::DerefMut::deref_mut(&mut target)))
+                }
+                #[allow(dead_code)]
+                pub async fn async_ref_handle<T, F, Out>(handle: T) -> anyhow::Result<Out>
+                where
+                    T: FnOnce(&mut $type_name) -> F,
+                    F: std::future::Future<Output = Out>,
+                {
+                    let this = Self::global().await?;
+                    let mut target = this.lock().await;
+                    Ok(handle(std::ops::DerefMut::deref_mut(&mut target)).await)
+                }
+                #[allow(dead_code)]
+                pub fn global_set(t: $type_name) {
+                    [<__ $type_name _ASYNC_GLOBAL>].set($crate::sync::AsyncMutex::new(t));
+                }
+            }
+        }
+    };
+    ($type_name:ident,rwlock,$init_func:block) => {
+        paste::paste! {
+            #[allow(non_snake_case,non_upper_case_globals)]
+            static [<__ $type_name _ASYNC_GLOBAL>]: $crate::sync::global::AsyncGlobal<tokio::sync::RwLock<$type_name>> = $crate::sync::global::AsyncGlobal::new();
+
+            #[allow(non_snake_case)]
+            async fn [<_init_ $type_name>]() -> anyhow::Result<tokio::sync::RwLock<$type_name>> {
+                let t: anyhow::Result<$type_name> = async move $init_func.await;
+                Ok(tokio::sync::RwLock::new(t?))
+            }
+
+            global_async!(@common $type_name, tokio::sync::RwLock<$type_name>);
+
+            impl $type_name {
+                #[allow(dead_code)]
+                pub async fn async_read<T, Out>(handle: T) -> anyhow::Result<Out>
+                where
+                    T: FnOnce(&$type_name) -> Out,
+                {
+                    let this = Self::global().await?;
+                    let target = this.read().await;
+                    Ok(handle(std::ops::Deref::deref(&target)))
+                }
+                #[allow(dead_code)]
+                pub async fn async_write<T, Out>(handle: T) -> anyhow::Result<Out>
+                where
+                    T: FnOnce(&mut $type_name) -> Out,
+                {
+                    let this = Self::global().await?;
+                    let mut target = this.write().await;
+                    Ok(handle(std::ops::DerefMut::deref_mut(&mut target)))
+                }
+                #[allow(dead_code)]
+                pub async fn async_write_handle<T, F, Out>(handle: T) -> anyhow::Result<Out>
+                where
+                    T: FnOnce(&mut $type_name) -> F,
+                    F: std::future::Future<Output = Out>,
+                {
+                    let this = Self::global().await?;
+                    let mut target = this.write().await;
+                    Ok(handle(std::ops::DerefMut::deref_mut(&mut target)).await)
+                }
+                #[allow(dead_code)]
+                pub fn global_set(t: $type_name) {
+                    [<__ $type_name _ASYNC_GLOBAL>].set(tokio::sync::RwLock::new(t));
+                }
+            }
+        }
+    };
+    (@common $type_name:ident, $lock:ty) => {
+        paste::paste! {
+            impl $type_name {
+                #[allow(dead_code)]
+                pub async fn global() -> anyhow::Result<std::sync::Arc<$lock>> {
+                    [<__ $type_name _ASYNC_GLOBAL>]
+                        .get_or_try_init([<_init_ $type_name>])
+                        .await
+                        .map_err(|e| e.context(format!("{} init failed", stringify!($type_name))))
+                }
+                #[allow(dead_code)]
+                pub async fn global_reinit() -> anyhow::Result<()> {
+                    [<__ $type_name _ASYNC_GLOBAL>]
+                        .try_reinit([<_init_ $type_name>])
+                        .await
+                        .map_err(|e| e.context(format!("{} init failed", stringify!($type_name))))?;
+                    Ok(())
+                }
+                #[allow(dead_code)]
+                pub fn global_reset() {
+                    [<__ $type_name _ASYNC_GLOBAL>].reset();
+                }
+            }
+        }
+    };
+}
+
 #[cfg(test)]
 mod test {
     use crate::sync::WaitGroup;
@@ -133,4 +323,63 @@ mod test {
         println!("age result = {}", TestStruct::unsafe_mut_ptr(|x| x.age));
         println!("use time = {}ms", use_time.elapsed().as_millis())
     }
+
+    struct AsyncStruct {
+        count: usize,
+    }
+
+    global_async!(AsyncStruct, {
+        tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        Ok(AsyncStruct { count: 1 })
+    });
+
+    struct RwStruct {
+        name: String,
+    }
+
+    static RW_STRUCT_FAIL: std::sync::atomic::AtomicBool = std::sync::atomic::AtomicBool::new(true);
+
+    global_async!(RwStruct, rwlock, {
+        if RW_STRUCT_FAIL.swap(false, std::sync::atomic::Ordering::Relaxed) {
+            return Err(anyhow::anyhow!("connect failed"));
+        }
+        Ok(RwStruct {
+            name: "hello".into(),
+        })
+    });
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_global_async() {
+        let wg = WaitGroup::default();
+        for _ in 0..10 {
+            wg.defer(move || async move {
+                AsyncStruct::async_ref(|x| x.count += 1).await.unwrap();
+            });
+        }
+        wg.wait().await;
+        let count = AsyncStruct::async_ref(|x| x.count).await.unwrap();
+        assert_eq!(count, 11);
+
+        AsyncStruct::global_reinit().await.unwrap();
+        let count = AsyncStruct::async_ref(|x| x.count).await.unwrap();
+        assert_eq!(count, 1);
+
+        AsyncStruct::global_set(AsyncStruct { count: 100 });
+        let count = AsyncStruct::async_ref(|x| x.count).await.unwrap();
+        assert_eq!(count, 100);
+    }
+
+    #[tokio::test]
+    async fn test_global_async_rwlock() {
+        let err = RwStruct::async_read(|x| x.name.clone()).await;
+        assert!(err.is_err());
+        let name = RwStruct::async_read(|x| x.name.clone()).await.unwrap();
+        assert_eq!(name, "hello");
+        RwStruct::async_write(|x| x.name = "world".into()).await.unwrap();
+        let name = RwStruct::async_read(|x| x.name.clone()).await.unwrap();
+        assert_eq!(name, "world");
+        RwStruct::global_reset();
+        let name = RwStruct::async_read(|x| x.name.clone()).await.unwrap();
+        assert_eq!(name, "hello");
+    }
 }
@@ -0,0 +1,92 @@
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::Duration;
+use tokio::sync::Notify;
+
+/// 倒计数门闩
+/// 计数减到0后，所有等待者放行，之后的等待直接返回
+pub struct CountDownLatch {
+    count: Arc<Mutex<usize>>,
+    notify: Arc<Notify>,
+    cond: Arc<Condvar>,
+}
+
+impl Clone for CountDownLatch {
+    fn clone(&self) -> Self {
+        Self {
+            count: self.count.clone(),
+            notify: self.notify.clone(),
+            cond: self.cond.clone(),
+        }
+    }
+}
+
+impl CountDownLatch {
+    pub fn new(count: usize) -> Self {
+        Self {
+            count: Arc::new(Mutex::new(count)),
+            notify: Arc::new(Notify::new()),
+            cond: Arc::new(Condvar::new()),
+        }
+    }
+    pub fn count(&self) -> usize {
+        *self.count.lock().unwrap()
+    }
+    pub fn count_down(&self) {
+        let mut count = self.count.lock().unwrap();
+        if *count == 0 {
+            return;
+        }
+        *count -= 1;
+        if *count == 0 {
+            drop(count);
+            self.notify.notify_waiters();
+            self.cond.notify_all();
+        }
+    }
+    pub async fn await_zero(&self) {
+        loop {
+            let notified = self.notify.notified();
+            if self.count() == 0 {
+                return;
+            }
+            notified.await;
+        }
+    }
+    pub fn blocking_await_zero(&self) {
+        let count = self.count.lock().unwrap();
+        let _count = self.cond.wait_while(count, |c| *c > 0).unwrap();
+    }
+    // return false if timeout
+    pub fn blocking_await_zero_timeout(&self, timeout: Duration) -> bool {
+        let count = self.count.lock().unwrap();
+        let (_count, res) = self
+            .cond
+            .wait_timeout_while(count, timeout, |c| *c > 0)
+            .unwrap();
+        !res.timed_out()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::sync::CountDownLatch;
+    use std::time::Duration;
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_count_down_latch() {
+        let latch = CountDownLatch::new(3);
+        for i in 0..3 {
+            let latch = latch.clone();
+            tokio::spawn(async move {
+                tokio::time::sleep(Duration::from_millis(100 * i)).await;
+                latch.count_down();
+            });
+        }
+        let blocking = latch.clone();
+        let thread = std::thread::spawn(move || blocking.blocking_await_zero());
+        latch.await_zero().await;
+        assert_eq!(latch.count(), 0);
+        thread.join().unwrap();
+        assert!(latch.blocking_await_zero_timeout(Duration::from_millis(1)));
+    }
+}
@@ -1,6 +1,9 @@
 mod async_lru;
 mod async_mutex;
+mod barrier;
 mod copy_lock;
+mod event;
+mod latch;
 mod null_lock;
 mod wait_group;
 #[macro_use]
@@ -9,7 +12,10 @@ mod lru;
 
 pub use async_lru::*;
 pub use async_mutex::*;
+pub use barrier::*;
 pub use copy_lock::*;
+pub use event::*;
+pub use latch::*;
 pub use lru::LruCache;
 pub use null_lock::*;
 pub use wait_group::*;